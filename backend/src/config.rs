@@ -0,0 +1,366 @@
+//! Runtime configuration of the backend service.
+//!
+//! The configuration is read from several sources, where later sources
+//! overwrite settings of earlier ones:
+//!
+//! 1. Default values (see constants in the crate root)
+//! 2. TOML configuration file (`--config`, `EINGANG_CONFIG` or
+//!    `$XDG_CONFIG_HOME/eingang/config.toml`)
+//! 3. Environment variables (`EINGANG_HOST`, `EINGANG_PORT`,
+//!    `EINGANG_DATA_DIR`, `EINGANG_STORAGE`, `EINGANG_CORS_ORIGINS`,
+//!    `EINGANG_LOG_LEVEL`, `EINGANG_TIME_ZONE`)
+//! 4. Command-line flags
+//!
+//! An example configuration file:
+//!
+//! ```toml
+//! host = "0.0.0.0"
+//! port = 8081
+//! data_dir = "/home/user/eingang"
+//! storage = "sqlite"
+//! cors_origins = ["http://localhost:8080"]
+//! log_level = "actix_web=info"
+//! time_zone = "Europe/Berlin"
+//!
+//! # Coefficients of the task urgency, only available in the file
+//! [urgency]
+//! priority_high = 6.0
+//! due = 12.0
+//! ```
+use crate::io::Backend;
+use crate::{APP_NAME, CONFIG_FILE, FRONTEND_HOST, FRONTEND_PORT, HOST, LOG_LEVEL, PORT};
+use chrono_tz::Tz;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use eingang::models::UrgencyCoefficients;
+use serde::Deserialize;
+use std::io::{Error, ErrorKind, Result};
+use std::path::PathBuf;
+
+/// Complete configuration of the backend
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub host: String,
+    pub port: u16,
+    pub data_dir: PathBuf,
+    pub storage: Backend,
+    pub cors_origins: Vec<String>,
+    pub log_level: String,
+    /// Time zone of dates without offset, e.g. `after=2020-10-01` in the
+    /// journal means after midnight at the start of that day in this zone
+    pub time_zone: Tz,
+    pub urgency: UrgencyCoefficients,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            host: HOST.to_string(),
+            port: PORT,
+            data_dir: default_data_dir(),
+            storage: Backend::default(),
+            cors_origins: vec![format!("{}:{}", FRONTEND_HOST, FRONTEND_PORT)],
+            log_level: LOG_LEVEL.to_string(),
+            time_zone: Tz::UTC,
+            urgency: UrgencyCoefficients::default(),
+        }
+    }
+}
+
+/// A single source of configuration, where every setting is optional
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigLayer {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub data_dir: Option<PathBuf>,
+    pub storage: Option<Backend>,
+    pub cors_origins: Option<Vec<String>>,
+    pub log_level: Option<String>,
+    pub time_zone: Option<Tz>,
+    pub urgency: Option<UrgencyCoefficients>,
+}
+
+impl Config {
+    /// Load the configuration from the file, the environment of the process
+    /// and the parsed command-line arguments
+    pub fn load(matches: &ArgMatches) -> Result<Self> {
+        Config::from_sources(matches, |k| std::env::var(k).ok())
+    }
+
+    /// Build the configuration from parsed command-line arguments and a
+    /// lookup function for environment variables
+    pub fn from_sources<F>(matches: &ArgMatches, env: F) -> Result<Self>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let file = match matches.value_of("config").map(PathBuf::from) {
+            Some(path) => Some(path),
+            None => env("EINGANG_CONFIG").map(PathBuf::from),
+        };
+        let file_layer = match file {
+            Some(path) => ConfigLayer::from_file(&path)?,
+            None => match default_config_file() {
+                Some(path) if path.is_file() => ConfigLayer::from_file(&path)?,
+                _ => ConfigLayer::default(),
+            },
+        };
+        let mut config = Config::default();
+        config.apply(file_layer);
+        config.apply(ConfigLayer::from_env(env)?);
+        config.apply(ConfigLayer::from_matches(matches)?);
+        Ok(config)
+    }
+
+    /// Overwrite all settings which are set in the layer
+    pub fn apply(&mut self, layer: ConfigLayer) {
+        if let Some(host) = layer.host {
+            self.host = host;
+        }
+        if let Some(port) = layer.port {
+            self.port = port;
+        }
+        if let Some(data_dir) = layer.data_dir {
+            self.data_dir = data_dir;
+        }
+        if let Some(storage) = layer.storage {
+            self.storage = storage;
+        }
+        if let Some(cors_origins) = layer.cors_origins {
+            self.cors_origins = cors_origins;
+        }
+        if let Some(log_level) = layer.log_level {
+            self.log_level = log_level;
+        }
+        if let Some(time_zone) = layer.time_zone {
+            self.time_zone = time_zone;
+        }
+        if let Some(urgency) = layer.urgency {
+            self.urgency = urgency;
+        }
+    }
+
+    /// Address the server binds to
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+impl ConfigLayer {
+    pub fn from_file(path: &std::path::Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        toml::from_str(&text)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn from_env<F>(env: F) -> Result<Self>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        Ok(ConfigLayer {
+            host: env("EINGANG_HOST"),
+            port: env("EINGANG_PORT").map(|p| parse_port(&p)).transpose()?,
+            data_dir: env("EINGANG_DATA_DIR").map(PathBuf::from),
+            storage: env("EINGANG_STORAGE").map(|s| s.parse()).transpose()?,
+            cors_origins: env("EINGANG_CORS_ORIGINS").map(|o| split_origins(&o)),
+            log_level: env("EINGANG_LOG_LEVEL"),
+            time_zone: env("EINGANG_TIME_ZONE")
+                .map(|z| parse_time_zone(&z))
+                .transpose()?,
+            urgency: None,
+        })
+    }
+
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self> {
+        Ok(ConfigLayer {
+            host: matches.value_of("host").map(String::from),
+            port: matches.value_of("port").map(parse_port).transpose()?,
+            data_dir: matches.value_of("data-dir").map(PathBuf::from),
+            storage: matches.value_of("storage").map(|s| s.parse()).transpose()?,
+            cors_origins: matches
+                .values_of("cors-origin")
+                .map(|v| v.map(String::from).collect()),
+            log_level: matches.value_of("log-level").map(String::from),
+            time_zone: matches
+                .value_of("time-zone")
+                .map(parse_time_zone)
+                .transpose()?,
+            urgency: None,
+        })
+    }
+}
+
+/// Command-line interface of the backend binary
+pub fn cli() -> App<'static, 'static> {
+    App::new("eingang-backend")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about(env!("CARGO_PKG_DESCRIPTION"))
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .short("c")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Path to the TOML configuration file"),
+        )
+        .arg(
+            Arg::with_name("host")
+                .long("host")
+                .takes_value(true)
+                .help("Address to bind the server to"),
+        )
+        .arg(
+            Arg::with_name("port")
+                .long("port")
+                .short("p")
+                .takes_value(true)
+                .help("Port to bind the server to"),
+        )
+        .arg(
+            Arg::with_name("data-dir")
+                .long("data-dir")
+                .short("d")
+                .takes_value(true)
+                .value_name("DIR")
+                .help("Folder for all saved data"),
+        )
+        .arg(
+            Arg::with_name("storage")
+                .long("storage")
+                .takes_value(true)
+                .possible_values(&["folder", "sqlite", "memory"])
+                .help("Storage backend"),
+        )
+        .arg(
+            Arg::with_name("cors-origin")
+                .long("cors-origin")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("ORIGIN")
+                .help("Allowed CORS origin (can be given multiple times)"),
+        )
+        .arg(
+            Arg::with_name("log-level")
+                .long("log-level")
+                .takes_value(true)
+                .value_name("FILTER")
+                .help("Log level, e.g. `info` or `actix_web=debug`"),
+        )
+        .arg(
+            Arg::with_name("time-zone")
+                .long("time-zone")
+                .takes_value(true)
+                .value_name("ZONE")
+                .help("Time zone of dates without offset, e.g. `Europe/Berlin`"),
+        )
+        .subcommand(
+            SubCommand::with_name("fsck")
+                .about("Check the references between threads and their elements")
+                .arg(
+                    Arg::with_name("repair")
+                        .long("repair")
+                        .help("Remove dangling and duplicate references from threads"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("find")
+                .about("Print all notes, tasks and threads matching a query")
+                .arg(
+                    Arg::with_name("query")
+                        .required(true)
+                        .multiple(true)
+                        .value_name("QUERY")
+                        .help("Query, e.g. `type:task status:open invoice -draft`"),
+                ),
+        )
+}
+
+fn parse_port(port: &str) -> Result<u16> {
+    port.parse()
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, format!("Invalid port '{}'", port)))
+}
+
+fn parse_time_zone(zone: &str) -> Result<Tz> {
+    zone.parse().map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Unknown time zone '{}'", zone),
+        )
+    })
+}
+
+fn split_origins(origins: &str) -> Vec<String> {
+    origins
+        .split(',')
+        .map(str::trim)
+        .filter(|o| !o.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn default_data_dir() -> PathBuf {
+    dirs::data_dir()
+        .map(|d| d.join(APP_NAME))
+        .unwrap_or_else(|| PathBuf::from(APP_NAME))
+}
+
+fn default_config_file() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join(APP_NAME).join(CONFIG_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cli, Config};
+    use crate::io::Backend;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    #[test]
+    fn layers_overwrite_in_order() {
+        let file = std::env::temp_dir().join(format!("eingang-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(
+            &file,
+            "port = 9000\nstorage = \"sqlite\"\nhost = \"0.0.0.0\"\ntime_zone = \"Europe/Berlin\"\n[urgency]\ndue = 1.5\n",
+        )
+        .unwrap();
+
+        let mut env = HashMap::new();
+        env.insert("EINGANG_PORT", "9001".to_string());
+        env.insert("EINGANG_CORS_ORIGINS", "http://a, http://b".to_string());
+        let matches = cli().get_matches_from(vec![
+            "eingang-backend",
+            "--config",
+            file.to_str().unwrap(),
+            "--port",
+            "9002",
+            "--data-dir",
+            "/srv/eingang",
+        ]);
+        let config = Config::from_sources(&matches, |k| env.get(k).cloned()).unwrap();
+        std::fs::remove_file(&file).unwrap();
+
+        assert_eq!(config.host, "0.0.0.0");
+        assert_eq!(config.storage, Backend::Sqlite);
+        assert_eq!(config.port, 9002);
+        assert_eq!(config.data_dir, PathBuf::from("/srv/eingang"));
+        assert_eq!(config.cors_origins, vec!["http://a", "http://b"]);
+        assert_eq!(config.time_zone, chrono_tz::Europe::Berlin);
+        assert_eq!(config.urgency.due, 1.5);
+        assert_eq!(config.urgency.age, 2.0);
+    }
+
+    #[test]
+    fn invalid_settings_are_errors() {
+        let matches = cli().get_matches_from(vec!["eingang-backend"]);
+        let env = |k: &str| match k {
+            "EINGANG_PORT" => Some("port".to_string()),
+            _ => None,
+        };
+        assert!(Config::from_sources(&matches, env).is_err());
+        let env = |k: &str| match k {
+            "EINGANG_TIME_ZONE" => Some("Mars/Olympus".to_string()),
+            _ => None,
+        };
+        assert!(Config::from_sources(&matches, env).is_err());
+    }
+}
@@ -0,0 +1,114 @@
+//! Errors of the storage layer and their mapping to HTTP responses.
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use eingang::models::{ErrorKind, ErrorResponse};
+use std::fmt::{Display, Formatter};
+
+/// Error returned by all `io` functions and route handlers
+#[derive(Debug)]
+pub enum EingangError {
+    /// The requested element does not exist
+    NotFound(String),
+    /// The given string is not a valid UUID
+    InvalidUuid(String),
+    /// The request is malformed, e.g. a required field is missing
+    BadRequest(String),
+    /// The request conflicts with the current state of an element
+    Conflict(String),
+    /// A saved element could not be parsed
+    Corrupt(String),
+    /// Any other error of the underlying storage
+    Io(std::io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, EingangError>;
+
+impl EingangError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            EingangError::NotFound(_) => ErrorKind::NotFound,
+            EingangError::InvalidUuid(_) => ErrorKind::InvalidUuid,
+            EingangError::BadRequest(_) => ErrorKind::BadRequest,
+            EingangError::Conflict(_) => ErrorKind::Conflict,
+            EingangError::Corrupt(_) => ErrorKind::Corrupt,
+            EingangError::Io(_) => ErrorKind::Io,
+        }
+    }
+    pub fn to_response(&self) -> ErrorResponse {
+        ErrorResponse::new(self.status_code().as_u16(), self.kind(), self.to_string())
+    }
+}
+
+impl Display for EingangError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EingangError::NotFound(s) => write!(f, "{} not found", s),
+            EingangError::InvalidUuid(s) => write!(f, "'{}' is not a valid UUID", s),
+            EingangError::BadRequest(s) => write!(f, "{}", s),
+            EingangError::Conflict(s) => write!(f, "{}", s),
+            EingangError::Corrupt(s) => write!(f, "Corrupt data: {}", s),
+            EingangError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for EingangError {}
+
+impl From<std::io::Error> for EingangError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => EingangError::NotFound(e.to_string()),
+            std::io::ErrorKind::InvalidData => EingangError::Corrupt(e.to_string()),
+            _ => EingangError::Io(e),
+        }
+    }
+}
+
+impl From<serde_json::Error> for EingangError {
+    fn from(e: serde_json::Error) -> Self {
+        EingangError::Corrupt(e.to_string())
+    }
+}
+
+impl From<serde_yaml::Error> for EingangError {
+    fn from(e: serde_yaml::Error) -> Self {
+        EingangError::Corrupt(e.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for EingangError {
+    fn from(e: rusqlite::Error) -> Self {
+        EingangError::Io(std::io::Error::other(e))
+    }
+}
+
+impl From<EingangError> for std::io::Error {
+    fn from(e: EingangError) -> Self {
+        match e {
+            EingangError::Io(e) => e,
+            EingangError::NotFound(_) => std::io::Error::new(std::io::ErrorKind::NotFound, e),
+            EingangError::Corrupt(_) => std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+            _ => std::io::Error::new(std::io::ErrorKind::InvalidInput, e),
+        }
+    }
+}
+
+impl ResponseError for EingangError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            EingangError::NotFound(_) => StatusCode::NOT_FOUND,
+            EingangError::InvalidUuid(_) | EingangError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            EingangError::Conflict(_) => StatusCode::CONFLICT,
+            EingangError::Corrupt(_) | EingangError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(self.to_response())
+    }
+}
+
+/// Parse a UUID given by the user
+pub fn parse_uuid(uuid: &str) -> Result<uuid::Uuid> {
+    uuid.parse()
+        .map_err(|_| EingangError::InvalidUuid(uuid.to_string()))
+}
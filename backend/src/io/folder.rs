@@ -0,0 +1,528 @@
+//! Storage backend using one folder per content type.
+//!
+//! Every element is saved as a single file named after its UUID. Notes and
+//! tasks are Markdown files with YAML front matter (see [`markdown`]),
+//! threads are saved as JSON.
+//!
+//! All files are written atomically: the content goes to a temporary file in
+//! the same folder, which is synced and then renamed over the target. A crash
+//! therefore leaves either the old or the new version, never a partial file.
+//!
+//! Files which cannot be parsed, as well as foreign files in the folders, are
+//! moved to `quarantine/<content type>/`. Next to each of them a
+//! `<name>.quarantine` file records the reason as JSON. Hidden files (e.g.
+//! `.DS_Store` or temporary files of running writes) are ignored.
+//!
+//! Before a note or task is overwritten, the previous file is kept as
+//! `revisions/<content type>/<uuid>/<n>.md`, numbered from 1 (oldest).
+//!
+//! [`markdown`]: super::markdown
+use super::{markdown, EingangError, Result, Storage};
+use crate::{NOTE_FOLDER, QUARANTINE_FOLDER, REVISION_FOLDER, TASK_FOLDER, THREAD_FOLDER};
+use eingang::models::{Idable, Note, Quarantined, Task, Thread};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Information about the location of each content type
+pub enum Location {
+    Note,
+    Thread,
+    Task,
+}
+
+impl Location {
+    pub fn get_basefolder(&self, base: &Path) -> PathBuf {
+        match self {
+            Location::Note => base.join(NOTE_FOLDER),
+            Location::Task => base.join(TASK_FOLDER),
+            Location::Thread => base.join(THREAD_FOLDER),
+        }
+    }
+    /// File extension of the content type. Notes and tasks are saved as
+    /// Markdown with YAML front matter, threads as JSON.
+    pub fn get_extension(&self) -> &'static str {
+        match self {
+            Location::Note | Location::Task => "md",
+            Location::Thread => "json",
+        }
+    }
+    /// Name of the content type used in messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            Location::Note => "Note",
+            Location::Task => "Task",
+            Location::Thread => "Thread",
+        }
+    }
+    pub fn create_filename(&self, base: &Path, uuid: &str) -> PathBuf {
+        let filename = format!("{}.{}", uuid, self.get_extension());
+        self.get_basefolder(base).join(filename)
+    }
+}
+
+/// Storage of all elements as files below a base folder
+pub struct FolderStorage {
+    base: PathBuf,
+}
+
+impl FolderStorage {
+    pub fn new<P: Into<PathBuf>>(base: P) -> Self {
+        FolderStorage { base: base.into() }
+    }
+    /// Create the storage and the folders for all content types
+    pub fn create<P: Into<PathBuf>>(base: P) -> Result<Self> {
+        let storage = FolderStorage::new(base);
+        for location in &[Location::Note, Location::Task, Location::Thread] {
+            std::fs::create_dir_all(location.get_basefolder(&storage.base))?;
+        }
+        Ok(storage)
+    }
+    fn filename(&self, location: &Location, uuid: &Uuid) -> PathBuf {
+        location.create_filename(&self.base, &uuid.to_string())
+    }
+    fn get<T, F>(&self, location: Location, uuid: &Uuid, read: F) -> Result<T>
+    where
+        F: Fn(&Path) -> Result<T>,
+    {
+        let file = self.filename(&location, uuid);
+        if !file.is_file() {
+            return Err(EingangError::NotFound(format!(
+                "{} {}",
+                location.name(),
+                uuid
+            )));
+        }
+        read(&file).map_err(|e| self.quarantine_on_corrupt(&location, &file, e))
+    }
+    /// Read all elements of the location. Corrupt and unexpected files are
+    /// quarantined, files removed in the meantime are gone and other files,
+    /// which cannot be read, are skipped, so that they never fail the listing.
+    fn list<T, F>(&self, location: Location, read: F) -> Result<Vec<T>>
+    where
+        F: Fn(&Path) -> Result<T>,
+    {
+        let folder = location.get_basefolder(&self.base);
+        let mut elements = Vec::new();
+        for entry in std::fs::read_dir(folder)? {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => {
+                    log::warn!("Skipping an entry of the {} folder: {}", location.name(), e);
+                    continue;
+                }
+            };
+            if is_hidden(&path) || path.is_dir() {
+                continue;
+            }
+            let result = if path.extension().and_then(|e| e.to_str())
+                != Some(location.get_extension())
+            {
+                let error = format!("Unexpected file in {} folder", location.name());
+                self.quarantine(&location, &path, error)
+            } else {
+                match read(&path) {
+                    Ok(element) => {
+                        elements.push(element);
+                        continue;
+                    }
+                    Err(EingangError::Corrupt(error)) => self.quarantine(&location, &path, error),
+                    Err(e) => Err(e),
+                }
+            };
+            match result {
+                Ok(()) | Err(EingangError::NotFound(_)) => {}
+                Err(e) => log::warn!("Skipping {}: {}", path.display(), e),
+            }
+        }
+        Ok(elements)
+    }
+    fn quarantine_on_corrupt(
+        &self,
+        location: &Location,
+        file: &Path,
+        e: EingangError,
+    ) -> EingangError {
+        match e {
+            EingangError::Corrupt(error) => match self.quarantine(location, file, error.clone()) {
+                Ok(()) => EingangError::Corrupt(error),
+                Err(e) => e,
+            },
+            e => e,
+        }
+    }
+    /// Move an unreadable file to the quarantine folder and record the reason
+    fn quarantine(&self, location: &Location, file: &Path, error: String) -> Result<()> {
+        let folder = location.get_basefolder(&self.base.join(QUARANTINE_FOLDER));
+        std::fs::create_dir_all(&folder)?;
+        let name = file
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        let mut target = folder.join(name);
+        let mut counter = 1;
+        while target.exists() {
+            target = folder.join(format!("{}.{}", name, counter));
+            counter += 1;
+        }
+        let target_name = target
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        log::warn!("Moving {} to quarantine: {}", file.display(), error);
+
+        let entry = Quarantined::new(location.name(), target_name, error);
+        let record = folder.join(format!("{}.quarantine", target_name));
+        write_atomic(&record, &serde_json::to_vec_pretty(&entry)?)?;
+        Ok(std::fs::rename(file, &target)?)
+    }
+    fn delete(&self, location: Location, uuid: &Uuid) -> Result<()> {
+        let file = self.filename(&location, uuid);
+        match std::fs::remove_file(file) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(EingangError::NotFound(format!(
+                    "{} {}",
+                    location.name(),
+                    uuid
+                )))
+            }
+            r => r?,
+        }
+        let revisions = self.revision_folder(&location, uuid);
+        if revisions.is_dir() {
+            std::fs::remove_dir_all(revisions)?;
+        }
+        Ok(())
+    }
+    fn revision_folder(&self, location: &Location, uuid: &Uuid) -> PathBuf {
+        location
+            .get_basefolder(&self.base.join(REVISION_FOLDER))
+            .join(uuid.to_string())
+    }
+    /// Numbered files of all revisions of an element, oldest first
+    fn revision_files(&self, location: &Location, uuid: &Uuid) -> Result<Vec<(usize, PathBuf)>> {
+        let folder = self.revision_folder(location, uuid);
+        if !folder.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(folder)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(location.get_extension()) {
+                continue;
+            }
+            if let Some(n) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse().ok())
+            {
+                files.push((n, path));
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+    /// Copy the current file of an element to its revisions, if it exists
+    fn keep_revision(&self, location: &Location, uuid: &Uuid) -> Result<()> {
+        let file = self.filename(location, uuid);
+        if !file.is_file() {
+            return Ok(());
+        }
+        let next = self
+            .revision_files(location, uuid)?
+            .last()
+            .map_or(1, |(n, _)| n + 1);
+        let folder = self.revision_folder(location, uuid);
+        std::fs::create_dir_all(&folder)?;
+        let target = folder.join(format!("{}.{}", next, location.get_extension()));
+        write_atomic(&target, &std::fs::read(file)?)
+    }
+    fn revisions<T, F>(&self, location: Location, uuid: &Uuid, read: F) -> Result<Vec<T>>
+    where
+        F: Fn(&Path) -> Result<T>,
+    {
+        self.revision_files(&location, uuid)?
+            .iter()
+            .map(|(_, file)| read(file))
+            .collect()
+    }
+}
+
+pub fn read_task_filepath(file: &Path) -> Result<Task> {
+    let text = std::fs::read_to_string(file)?;
+    markdown::from_markdown(&text).map_err(|e| corrupt(file, e))
+}
+
+pub fn read_note_filepath(file: &Path) -> Result<Note> {
+    let text = std::fs::read_to_string(file)?;
+    markdown::from_markdown(&text).map_err(|e| corrupt(file, e))
+}
+
+pub fn read_thread_filepath(file: &Path) -> Result<Thread> {
+    let buffer = File::open(file)?;
+    let rdr = std::io::BufReader::new(buffer);
+    serde_json::from_reader(rdr).map_err(|e| corrupt(file, e))
+}
+
+fn is_hidden(file: &Path) -> bool {
+    file.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|n| n.starts_with('.'))
+}
+
+fn corrupt<E: std::fmt::Display>(file: &Path, e: E) -> EingangError {
+    EingangError::Corrupt(format!("{}: {}", file.display(), e))
+}
+
+fn write_markdown<T: serde::Serialize>(file: &Path, element: &T) -> Result<()> {
+    let text = markdown::to_markdown(element)?;
+    write_atomic(file, text.as_bytes())
+}
+
+/// Replace the content of `file` atomically.
+///
+/// The content is written to a temporary file next to `file`, synced to disk
+/// and renamed over `file`. Afterwards the folder is synced as well, so the
+/// rename itself survives a power loss.
+pub fn write_atomic(file: &Path, content: &[u8]) -> Result<()> {
+    let folder = match file.parent() {
+        Some(f) if !f.as_os_str().is_empty() => f,
+        _ => Path::new("."),
+    };
+    let name = file
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    let temp = folder.join(format!(".{}.{}.tmp", name, Uuid::new_v4()));
+
+    let result = (|| {
+        let mut buffer = File::create(&temp)?;
+        buffer.write_all(content)?;
+        buffer.sync_all()?;
+        std::fs::rename(&temp, file)
+    })();
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&temp);
+        return Err(e.into());
+    }
+    sync_folder(folder)
+}
+
+#[cfg(unix)]
+fn sync_folder(folder: &Path) -> Result<()> {
+    Ok(File::open(folder)?.sync_all()?)
+}
+
+#[cfg(not(unix))]
+fn sync_folder(_: &Path) -> Result<()> {
+    Ok(())
+}
+
+impl Storage for FolderStorage {
+    fn get_note(&self, uuid: &Uuid) -> Result<Note> {
+        self.get(Location::Note, uuid, read_note_filepath)
+    }
+    fn list_notes(&self) -> Result<Vec<Note>> {
+        self.list(Location::Note, read_note_filepath)
+    }
+    fn save_note(&self, note: &Note) -> Result<()> {
+        let file = self.filename(&Location::Note, &note.get_uuid());
+        self.keep_revision(&Location::Note, &note.get_uuid())?;
+        write_markdown(&file, note)
+    }
+    fn delete_note(&self, uuid: &Uuid) -> Result<()> {
+        self.delete(Location::Note, uuid)
+    }
+
+    fn get_task(&self, uuid: &Uuid) -> Result<Task> {
+        self.get(Location::Task, uuid, read_task_filepath)
+    }
+    fn list_tasks(&self) -> Result<Vec<Task>> {
+        self.list(Location::Task, read_task_filepath)
+    }
+    fn save_task(&self, task: &Task) -> Result<()> {
+        let file = self.filename(&Location::Task, &task.get_uuid());
+        self.keep_revision(&Location::Task, &task.get_uuid())?;
+        write_markdown(&file, task)
+    }
+    fn delete_task(&self, uuid: &Uuid) -> Result<()> {
+        self.delete(Location::Task, uuid)
+    }
+
+    fn note_revisions(&self, uuid: &Uuid) -> Result<Vec<Note>> {
+        self.revisions(Location::Note, uuid, read_note_filepath)
+    }
+    fn task_revisions(&self, uuid: &Uuid) -> Result<Vec<Task>> {
+        self.revisions(Location::Task, uuid, read_task_filepath)
+    }
+
+    fn get_thread(&self, uuid: &Uuid) -> Result<Thread> {
+        self.get(Location::Thread, uuid, read_thread_filepath)
+    }
+    fn list_threads(&self) -> Result<Vec<Thread>> {
+        self.list(Location::Thread, read_thread_filepath)
+    }
+    fn save_thread(&self, thread: &Thread) -> Result<()> {
+        let file = self.filename(&Location::Thread, &thread.get_uuid());
+        write_atomic(&file, &serde_json::to_vec_pretty(thread)?)
+    }
+    fn delete_thread(&self, uuid: &Uuid) -> Result<()> {
+        self.delete(Location::Thread, uuid)
+    }
+
+    fn list_quarantined(&self) -> Result<Vec<Quarantined>> {
+        let base = self.base.join(QUARANTINE_FOLDER);
+        let mut entries = Vec::new();
+        for location in &[Location::Note, Location::Task, Location::Thread] {
+            let folder = location.get_basefolder(&base);
+            if !folder.is_dir() {
+                continue;
+            }
+            for entry in std::fs::read_dir(folder)?.flatten() {
+                let path = entry.path();
+                if path.extension().and_then(|e| e.to_str()) == Some("quarantine") {
+                    let record = std::fs::read_to_string(&path)
+                        .map_err(EingangError::from)
+                        .and_then(|text| Ok(serde_json::from_str(&text)?));
+                    // Report unreadable records instead of hiding the others
+                    entries.push(record.unwrap_or_else(|e| {
+                        let name = path
+                            .file_stem()
+                            .and_then(|n| n.to_str())
+                            .unwrap_or_default();
+                        let error = format!("Unreadable quarantine record: {}", e);
+                        Quarantined::new(location.name(), name, error)
+                    }));
+                }
+            }
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FolderStorage, Location};
+    use crate::io::Storage;
+    use eingang::models::{Idable, Note, Thread, ThreadItem};
+
+    #[test]
+    fn saving_leaves_no_temporary_files() {
+        let base = std::env::temp_dir().join(format!("eingang-{}", uuid::Uuid::new_v4()));
+        let store = FolderStorage::create(&base).unwrap();
+        let mut note = Note::with_title("first".to_string(), "title".to_string());
+        store.save_note(&note).unwrap();
+        note.content = "second".to_string();
+        store.save_note(&note).unwrap();
+
+        let folder = Location::Note.get_basefolder(&base);
+        std::fs::write(folder.join(".interrupted.md.tmp"), "---\nbroken").unwrap();
+        let notes = store.list_notes().unwrap();
+        let files = std::fs::read_dir(&folder).unwrap().count();
+        std::fs::remove_dir_all(&base).unwrap();
+
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].content, "second");
+        assert_eq!(files, 2);
+    }
+
+    #[test]
+    fn unreadable_files_are_quarantined() {
+        let base = std::env::temp_dir().join(format!("eingang-{}", uuid::Uuid::new_v4()));
+        let store = FolderStorage::create(&base).unwrap();
+        let note = Note::new("content".to_string());
+        store.save_note(&note).unwrap();
+
+        let folder = Location::Task.get_basefolder(&base);
+        std::fs::write(folder.join("broken.md"), "---\nstatus: [\n---\n").unwrap();
+        std::fs::write(folder.join("notes.txt"), "stray").unwrap();
+        std::fs::write(folder.join(".DS_Store"), "").unwrap();
+
+        let tasks = store.list_tasks().unwrap();
+        let quarantined = store.list_quarantined().unwrap();
+        let remaining = std::fs::read_dir(&folder).unwrap().count();
+        let notes = store.list_notes().unwrap();
+        std::fs::remove_dir_all(&base).unwrap();
+
+        assert!(tasks.is_empty());
+        assert_eq!(notes.len(), 1);
+        assert_eq!(remaining, 1);
+        assert_eq!(quarantined.len(), 2);
+        assert!(quarantined.iter().all(|q| q.kind == "Task"));
+    }
+
+    #[test]
+    fn vanished_files_and_broken_records_fail_no_listing() {
+        let base = std::env::temp_dir().join(format!("eingang-{}", uuid::Uuid::new_v4()));
+        let store = FolderStorage::create(&base).unwrap();
+        store.save_note(&Note::new("content".to_string())).unwrap();
+
+        // A link whose file is gone, as if it was deleted during the listing
+        let folder = Location::Note.get_basefolder(&base);
+        std::os::unix::fs::symlink(base.join("missing.md"), folder.join("gone.md")).unwrap();
+        let records = Location::Task.get_basefolder(&base.join(crate::QUARANTINE_FOLDER));
+        std::fs::create_dir_all(&records).unwrap();
+        std::fs::write(records.join("broken.md.quarantine"), "{").unwrap();
+
+        let notes = store.list_notes();
+        let quarantined = store.list_quarantined();
+        std::fs::remove_dir_all(&base).unwrap();
+
+        assert_eq!(notes.unwrap().len(), 1);
+        let quarantined = quarantined.unwrap();
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(quarantined[0].name, "broken.md");
+        assert!(quarantined[0].error.starts_with("Unreadable"));
+    }
+
+    #[test]
+    fn previous_versions_are_kept_as_revisions() {
+        let base = std::env::temp_dir().join(format!("eingang-{}", uuid::Uuid::new_v4()));
+        let store = FolderStorage::create(&base).unwrap();
+        let mut note = Note::new("first".to_string());
+        store.save_note(&note).unwrap();
+        note.content = "second".to_string();
+        store.save_note(&note).unwrap();
+        note.content = "third".to_string();
+        store.save_note(&note).unwrap();
+
+        let revisions = store.note_revisions(&note.get_uuid()).unwrap();
+        store.delete_note(&note.get_uuid()).unwrap();
+        let remaining = store.note_revisions(&note.get_uuid()).unwrap();
+        std::fs::remove_dir_all(&base).unwrap();
+
+        let contents: Vec<_> = revisions.iter().map(|n| n.content.as_str()).collect();
+        assert_eq!(contents, vec!["first", "second"]);
+        assert!(remaining.is_empty());
+    }
+
+    #[test]
+    fn threads_with_separate_lists_are_read() {
+        let base = std::env::temp_dir().join(format!("eingang-{}", uuid::Uuid::new_v4()));
+        let store = FolderStorage::create(&base).unwrap();
+        let uuid = "a1a2a3a4-b1b2-c1c2-d1d2-d3d4d5d6d7d8";
+        let json = r#"{
+            "notes": ["67e55044-10b1-426f-9247-bb680e5fe0c8"],
+            "tasks": ["936da01f-9abd-4d9d-80c7-02af85c822a8"],
+            "meta": {
+                "created_on": "2021-03-01T10:00:00Z",
+                "last_modified": "2021-03-01T10:00:00Z",
+                "uuid": "a1a2a3a4-b1b2-c1c2-d1d2-d3d4d5d6d7d8"
+            }
+        }"#;
+        std::fs::write(Location::Thread.create_filename(&base, uuid), json).unwrap();
+        let thread = store.get_thread(&uuid.parse().unwrap());
+        std::fs::remove_dir_all(&base).unwrap();
+
+        let items = thread.unwrap().items;
+        let created = Thread::with_tasks_and_notes(
+            vec!["936da01f-9abd-4d9d-80c7-02af85c822a8".parse().unwrap()],
+            vec!["67e55044-10b1-426f-9247-bb680e5fe0c8".parse().unwrap()],
+        );
+        assert_eq!(items.len(), 2);
+        assert!(matches!(items[0], ThreadItem::Task(_)));
+        assert!(matches!(items[1], ThreadItem::Note(_)));
+        assert_eq!(items, created.items);
+    }
+}
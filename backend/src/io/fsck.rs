@@ -0,0 +1,184 @@
+//! Checks of the references between threads and their notes and tasks.
+//!
+//! Threads only save the UUIDs of their elements and sub-threads. [`check`]
+//! finds references to elements or threads which do not exist, elements
+//! referenced several times by the same thread and elements which are not
+//! part of any thread. [`repair`] removes the broken references from the
+//! threads; orphaned elements are only reported. Elements matching the saved
+//! search of a thread are part of it, even though their UUIDs are not saved.
+use super::{query, Result, Storage};
+use eingang::models::{FsckReport, Idable, Problem, Thread, ThreadItem};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Find all problems without changing the storage
+pub fn check(store: &dyn Storage) -> Result<FsckReport> {
+    run(store, false)
+}
+
+/// Find all problems and remove broken references from the threads
+pub fn repair(store: &dyn Storage) -> Result<FsckReport> {
+    run(store, true)
+}
+
+/// Remove all references to the element or thread `uuid` from all threads and
+/// from the dependencies of all tasks
+pub fn detach(store: &dyn Storage, uuid: &Uuid) -> Result<()> {
+    for mut task in store.list_tasks()? {
+        if task.blocked_by.contains(uuid) {
+            task.blocked_by.retain(|b| b != uuid);
+            task.update_modified_date();
+            store.save_task(&task)?;
+        }
+    }
+    for mut thread in store.list_threads()? {
+        let count = thread.items.len();
+        thread.items.retain(|i| i.uuid() != *uuid);
+        if thread.items.len() != count {
+            thread.update_modified_date();
+            store.save_thread(&thread)?;
+        }
+    }
+    Ok(())
+}
+
+fn run(store: &dyn Storage, fix: bool) -> Result<FsckReport> {
+    let notes: HashSet<Uuid> = store.list_notes()?.iter().map(|n| n.get_uuid()).collect();
+    let tasks: HashSet<Uuid> = store.list_tasks()?.iter().map(|t| t.get_uuid()).collect();
+    let threads = store.list_threads()?;
+    let uuids: HashSet<Uuid> = threads.iter().map(|t| t.get_uuid()).collect();
+    let mut referenced = HashSet::new();
+    let mut report = FsckReport::default();
+
+    for mut thread in threads {
+        if thread.is_search() {
+            query::resolve_search(store, &mut thread)?;
+            referenced.extend(thread.items.iter().map(ThreadItem::uuid));
+            continue;
+        }
+        let problems = clean(&mut thread, &notes, &tasks, &uuids);
+        referenced.extend(thread.items.iter().map(ThreadItem::uuid));
+        if fix && !problems.is_empty() {
+            thread.update_modified_date();
+            store.save_thread(&thread)?;
+            report.repaired += problems.len();
+        }
+        report.problems.extend(problems);
+    }
+
+    let mut orphans: Vec<Problem> = notes
+        .difference(&referenced)
+        .map(|&note| Problem::OrphanedNote { note })
+        .chain(
+            tasks
+                .difference(&referenced)
+                .map(|&task| Problem::OrphanedTask { task }),
+        )
+        .collect();
+    orphans.sort_by_key(|p| format!("{:?}", p));
+    report.problems.extend(orphans);
+    Ok(report)
+}
+
+/// Remove dangling and duplicate references from the thread and return them
+fn clean(
+    thread: &mut Thread,
+    notes: &HashSet<Uuid>,
+    tasks: &HashSet<Uuid>,
+    threads: &HashSet<Uuid>,
+) -> Vec<Problem> {
+    let uuid = thread.get_uuid();
+    let mut problems = Vec::new();
+
+    let mut seen = HashSet::new();
+    thread.items.retain(|&item| {
+        let (exists, dangling, duplicate) = match item {
+            ThreadItem::Note(note) => (
+                notes.contains(&note),
+                Problem::DanglingNote { thread: uuid, note },
+                Problem::DuplicateNote { thread: uuid, note },
+            ),
+            ThreadItem::Task(task) => (
+                tasks.contains(&task),
+                Problem::DanglingTask { thread: uuid, task },
+                Problem::DuplicateTask { thread: uuid, task },
+            ),
+            ThreadItem::Thread(subthread) => (
+                threads.contains(&subthread),
+                Problem::DanglingThread {
+                    thread: uuid,
+                    subthread,
+                },
+                Problem::DuplicateThread {
+                    thread: uuid,
+                    subthread,
+                },
+            ),
+        };
+        if !exists {
+            problems.push(dangling);
+            false
+        } else if !seen.insert(item) {
+            problems.push(duplicate);
+            false
+        } else {
+            true
+        }
+    });
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, detach, repair};
+    use crate::io::{MemoryStorage, Storage};
+    use eingang::models::{Idable, Note, Problem, Task, Thread};
+
+    #[test]
+    fn find_and_repair_broken_references() {
+        let store = MemoryStorage::new();
+        let note = Note::new("note".to_string());
+        let orphan = Task::new("orphan".to_string());
+        let missing = uuid::Uuid::new_v4();
+        store.save_note(&note).unwrap();
+        store.save_task(&orphan).unwrap();
+        let thread =
+            Thread::with_tasks_and_notes(vec![missing], vec![note.get_uuid(), note.get_uuid()]);
+        store.save_thread(&thread).unwrap();
+
+        let report = check(&store).unwrap();
+        assert_eq!(report.repaired, 0);
+        assert_eq!(report.problems.len(), 3);
+        assert!(report.problems.contains(&Problem::OrphanedTask {
+            task: orphan.get_uuid()
+        }));
+
+        let report = repair(&store).unwrap();
+        assert_eq!(report.repaired, 2);
+        let thread = store.get_thread(&thread.get_uuid()).unwrap();
+        assert_eq!(thread.notes(), vec![note.get_uuid()]);
+        assert!(thread.tasks().is_empty());
+        assert_eq!(check(&store).unwrap().problems.len(), 1);
+
+        detach(&store, &note.get_uuid()).unwrap();
+        let thread = store.get_thread(&thread.get_uuid()).unwrap();
+        assert!(thread.items.is_empty());
+    }
+
+    #[test]
+    fn saved_searches_reference_their_elements() {
+        let store = MemoryStorage::new();
+        let task = Task::new("Call the plumber".to_string());
+        store.save_task(&task).unwrap();
+        assert_eq!(check(&store).unwrap().problems.len(), 1);
+
+        let thread = Thread::with_query("type:task plumber".parse().unwrap());
+        store.save_thread(&thread).unwrap();
+        assert!(repair(&store).unwrap().problems.is_empty());
+        assert!(store
+            .get_thread(&thread.get_uuid())
+            .unwrap()
+            .items
+            .is_empty());
+    }
+}
@@ -0,0 +1,137 @@
+//! Storage decorator keeping the indexes up to date.
+use super::links::LinkIndex;
+use super::search::SearchIndex;
+use super::{EingangError, Result, Storage};
+use eingang::models::eingang::Timestamp;
+use eingang::models::{Idable, Note, Quarantined, Task, TaskStatus, Thread};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Wrapper around any [`Storage`], which updates the indexes after every
+/// successful save or delete. All reads are passed to the inner storage,
+/// notes and tasks which cannot be read anymore (e.g. because they were
+/// quarantined) are removed from the indexes.
+pub struct IndexedStorage {
+    inner: Arc<dyn Storage>,
+    links: Arc<LinkIndex>,
+    search: Arc<SearchIndex>,
+}
+
+impl IndexedStorage {
+    /// Wrap `inner` and build all indexes from its content
+    pub fn build(inner: Arc<dyn Storage>) -> Result<Self> {
+        let links = Arc::new(LinkIndex::build(&*inner)?);
+        let search = Arc::new(SearchIndex::build(&*inner)?);
+        Ok(IndexedStorage {
+            inner,
+            links,
+            search,
+        })
+    }
+    pub fn links(&self) -> Arc<LinkIndex> {
+        self.links.clone()
+    }
+    pub fn search(&self) -> Arc<SearchIndex> {
+        self.search.clone()
+    }
+    /// Remove the element from the indexes, if it is missing or unreadable
+    fn forget_failed<T>(&self, uuid: &Uuid, result: Result<T>) -> Result<T> {
+        if let Err(EingangError::NotFound(_)) | Err(EingangError::Corrupt(_)) = result {
+            self.links.remove(uuid);
+            self.search.remove(uuid);
+        }
+        result
+    }
+}
+
+impl Storage for IndexedStorage {
+    fn get_note(&self, uuid: &Uuid) -> Result<Note> {
+        self.forget_failed(uuid, self.inner.get_note(uuid))
+    }
+    fn list_notes(&self) -> Result<Vec<Note>> {
+        self.inner.list_notes()
+    }
+    fn save_note(&self, note: &Note) -> Result<()> {
+        self.inner.save_note(note)?;
+        self.links
+            .update(note.get_uuid(), "Note", &note.title, &note.content);
+        self.search
+            .update(note.get_uuid(), "Note", &note.title, &note.content);
+        Ok(())
+    }
+    fn delete_note(&self, uuid: &Uuid) -> Result<()> {
+        self.inner.delete_note(uuid)?;
+        self.links.remove(uuid);
+        self.search.remove(uuid);
+        Ok(())
+    }
+
+    fn get_task(&self, uuid: &Uuid) -> Result<Task> {
+        self.forget_failed(uuid, self.inner.get_task(uuid))
+    }
+    fn list_tasks(&self) -> Result<Vec<Task>> {
+        self.inner.list_tasks()
+    }
+    fn save_task(&self, task: &Task) -> Result<()> {
+        self.inner.save_task(task)?;
+        self.links
+            .update(task.get_uuid(), "Task", &task.title, &task.content);
+        self.search
+            .update(task.get_uuid(), "Task", &task.title, &task.content);
+        Ok(())
+    }
+    fn delete_task(&self, uuid: &Uuid) -> Result<()> {
+        self.inner.delete_task(uuid)?;
+        self.links.remove(uuid);
+        self.search.remove(uuid);
+        Ok(())
+    }
+
+    fn note_revisions(&self, uuid: &Uuid) -> Result<Vec<Note>> {
+        self.inner.note_revisions(uuid)
+    }
+    fn task_revisions(&self, uuid: &Uuid) -> Result<Vec<Task>> {
+        self.inner.task_revisions(uuid)
+    }
+
+    fn get_thread(&self, uuid: &Uuid) -> Result<Thread> {
+        self.inner.get_thread(uuid)
+    }
+    fn list_threads(&self) -> Result<Vec<Thread>> {
+        self.inner.list_threads()
+    }
+    fn save_thread(&self, thread: &Thread) -> Result<()> {
+        self.inner.save_thread(thread)
+    }
+    fn delete_thread(&self, uuid: &Uuid) -> Result<()> {
+        self.inner.delete_thread(uuid)
+    }
+
+    fn list_notes_between(
+        &self,
+        before: Option<Timestamp>,
+        after: Option<Timestamp>,
+    ) -> Result<Vec<Note>> {
+        self.inner.list_notes_between(before, after)
+    }
+    fn list_tasks_between(
+        &self,
+        before: Option<Timestamp>,
+        after: Option<Timestamp>,
+    ) -> Result<Vec<Task>> {
+        self.inner.list_tasks_between(before, after)
+    }
+    fn list_threads_between(
+        &self,
+        before: Option<Timestamp>,
+        after: Option<Timestamp>,
+    ) -> Result<Vec<Thread>> {
+        self.inner.list_threads_between(before, after)
+    }
+    fn list_tasks_with_status(&self, status: TaskStatus) -> Result<Vec<Task>> {
+        self.inner.list_tasks_with_status(status)
+    }
+    fn list_quarantined(&self) -> Result<Vec<Quarantined>> {
+        self.inner.list_quarantined()
+    }
+}
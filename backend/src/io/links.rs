@@ -0,0 +1,186 @@
+//! Index of the wiki-style links between notes and tasks.
+//!
+//! The index is built from the storage at startup and kept up to date by
+//! [`IndexedStorage`] on every save and delete.
+//!
+//! [`IndexedStorage`]: super::IndexedStorage
+use super::{Result, Storage};
+use eingang::models::eingang::link::normalize_title;
+use eingang::models::{parse_links, Idable, LinkTarget, LinkedElement, LinksResponse};
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+use uuid::Uuid;
+
+/// Links of all notes and tasks, indexed in both directions
+#[derive(Default)]
+pub struct LinkIndex {
+    inner: RwLock<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    elements: HashMap<Uuid, Entry>,
+    /// Elements by normalized title
+    titles: HashMap<String, HashSet<Uuid>>,
+    /// Sources by normalized link target
+    backlinks: HashMap<LinkTarget, HashSet<Uuid>>,
+}
+
+struct Entry {
+    kind: &'static str,
+    title: String,
+    links: Vec<LinkTarget>,
+}
+
+impl LinkIndex {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    /// Build the index from all notes and tasks in the storage
+    pub fn build(store: &dyn Storage) -> Result<Self> {
+        let index = LinkIndex::new();
+        for note in store.list_notes()? {
+            index.update(note.get_uuid(), "Note", &note.title, &note.content);
+        }
+        for task in store.list_tasks()? {
+            index.update(task.get_uuid(), "Task", &task.title, &task.content);
+        }
+        Ok(index)
+    }
+    /// Add or replace the element `uuid` in the index
+    pub fn update(&self, uuid: Uuid, kind: &'static str, title: &str, content: &str) {
+        let mut inner = self.inner.write().unwrap();
+        inner.remove(&uuid);
+        let links = parse_links(content);
+        for link in &links {
+            inner
+                .backlinks
+                .entry(link.normalized())
+                .or_default()
+                .insert(uuid);
+        }
+        inner
+            .titles
+            .entry(normalize_title(title))
+            .or_default()
+            .insert(uuid);
+        let entry = Entry {
+            kind,
+            title: title.to_string(),
+            links,
+        };
+        inner.elements.insert(uuid, entry);
+    }
+    /// Remove the element `uuid` from the index
+    pub fn remove(&self, uuid: &Uuid) {
+        self.inner.write().unwrap().remove(uuid);
+    }
+    /// Return all elements `uuid` links to, as well as the unresolved links
+    pub fn links(&self, uuid: &Uuid) -> LinksResponse {
+        let inner = self.inner.read().unwrap();
+        let mut response = LinksResponse::default();
+        let links = match inner.elements.get(uuid) {
+            Some(entry) => &entry.links,
+            None => return response,
+        };
+        for link in links {
+            let targets = inner.resolve(link);
+            if targets.is_empty() {
+                response.unresolved.push(link.clone());
+            }
+            for target in targets {
+                if !response.links.contains(&target) {
+                    response.links.push(target);
+                }
+            }
+        }
+        response
+    }
+    /// Return all elements linking to `uuid`
+    pub fn backlinks(&self, uuid: &Uuid) -> Vec<LinkedElement> {
+        let inner = self.inner.read().unwrap();
+        let mut sources = HashSet::new();
+        if let Some(s) = inner.backlinks.get(&LinkTarget::Uuid(*uuid)) {
+            sources.extend(s);
+        }
+        if let Some(entry) = inner.elements.get(uuid) {
+            let title = LinkTarget::Title(normalize_title(&entry.title));
+            if let Some(s) = inner.backlinks.get(&title) {
+                sources.extend(s);
+            }
+        }
+        sources.remove(uuid);
+        let mut elements: Vec<LinkedElement> =
+            sources.iter().filter_map(|s| inner.element(s)).collect();
+        elements.sort_by(|a, b| a.title.cmp(&b.title));
+        elements
+    }
+}
+
+impl Inner {
+    fn remove(&mut self, uuid: &Uuid) {
+        if let Some(entry) = self.elements.remove(uuid) {
+            for link in entry.links {
+                let link = link.normalized();
+                if let Some(sources) = self.backlinks.get_mut(&link) {
+                    sources.remove(uuid);
+                    if sources.is_empty() {
+                        self.backlinks.remove(&link);
+                    }
+                }
+            }
+            let title = normalize_title(&entry.title);
+            if let Some(uuids) = self.titles.get_mut(&title) {
+                uuids.remove(uuid);
+                if uuids.is_empty() {
+                    self.titles.remove(&title);
+                }
+            }
+        }
+    }
+    fn element(&self, uuid: &Uuid) -> Option<LinkedElement> {
+        self.elements.get(uuid).map(|e| LinkedElement {
+            uuid: *uuid,
+            kind: e.kind.to_string(),
+            title: e.title.clone(),
+        })
+    }
+    fn resolve(&self, link: &LinkTarget) -> Vec<LinkedElement> {
+        match link.normalized() {
+            LinkTarget::Uuid(uuid) => self.element(&uuid).into_iter().collect(),
+            LinkTarget::Title(title) => self
+                .titles
+                .get(&title)
+                .map(|uuids| uuids.iter().filter_map(|u| self.element(u)).collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LinkIndex;
+    use eingang::models::LinkTarget;
+
+    #[test]
+    fn links_and_backlinks_follow_updates() {
+        let index = LinkIndex::new();
+        let (a, b) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+        index.update(a, "Note", "First", &format!("See [[second]] and [[{}]]", b));
+        index.update(b, "Task", "Second", "Nothing [[Missing]]");
+
+        let links = index.links(&a);
+        assert_eq!(links.links.len(), 1);
+        assert_eq!(links.links[0].uuid, b);
+        assert_eq!(
+            index.links(&b).unresolved,
+            vec![LinkTarget::Title("Missing".into())]
+        );
+        assert_eq!(index.backlinks(&b)[0].uuid, a);
+
+        index.update(a, "Note", "First", "No links anymore");
+        assert!(index.backlinks(&b).is_empty());
+        index.remove(&b);
+        assert!(index.links(&b).links.is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+//! Conversion between content types and Markdown files.
+//!
+//! Notes and tasks are saved as plain Markdown files. All fields apart from
+//! `content` are written as YAML front matter at the top of the file, while
+//! the `content` itself makes up the body:
+//!
+//! ```text
+//! ---
+//! title: Some title
+//! status: open
+//! created_on: "2020-10-01T10:00:00Z"
+//! last_modified: "2020-10-01T10:00:00Z"
+//! uuid: 5c5fa8a6-1a9b-4d38-a2ae-68e35b4d7d6a
+//! ---
+//! The content of the note as *Markdown*.
+//! ```
+//!
+//! The fields of `Meta` are flattened into the front matter, so the files stay
+//! easy to edit by hand.
+use serde::de::{DeserializeOwned, Error};
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+
+const DELIMITER: &str = "---";
+const CONTENT: &str = "content";
+const META: &str = "meta";
+const META_FIELDS: [&str; 3] = ["created_on", "last_modified", "uuid"];
+
+/// Serialize an element into a Markdown document with YAML front matter
+pub fn to_markdown<T: Serialize>(element: &T) -> Result<String, serde_yaml::Error> {
+    let mut mapping = match serde_yaml::to_value(element)? {
+        Value::Mapping(m) => m,
+        _ => return Err(serde_yaml::Error::custom("Element is not a mapping")),
+    };
+    let content = match mapping.remove(&key(CONTENT)) {
+        Some(Value::String(s)) => s,
+        _ => String::new(),
+    };
+    if let Some(Value::Mapping(meta)) = mapping.remove(&key(META)) {
+        for (k, v) in meta {
+            mapping.insert(k, v);
+        }
+    }
+    let yaml = serde_yaml::to_string(&mapping)?;
+    let yaml = yaml.trim_start_matches(DELIMITER).trim();
+    Ok(format!(
+        "{}\n{}\n{}\n{}",
+        DELIMITER, yaml, DELIMITER, content
+    ))
+}
+
+/// Deserialize an element from a Markdown document with YAML front matter
+pub fn from_markdown<T: DeserializeOwned>(text: &str) -> Result<T, serde_yaml::Error> {
+    let (front, body) = split_front_matter(text)
+        .ok_or_else(|| serde_yaml::Error::custom("Missing YAML front matter"))?;
+    let mut mapping: Mapping = serde_yaml::from_str(front)?;
+    let mut meta = Mapping::new();
+    for field in META_FIELDS.iter() {
+        if let Some(v) = mapping.remove(&key(field)) {
+            meta.insert(key(field), v);
+        }
+    }
+    mapping.insert(key(META), Value::Mapping(meta));
+    mapping.insert(key(CONTENT), Value::String(body.to_string()));
+    serde_yaml::from_value(Value::Mapping(mapping))
+}
+
+/// Split a document into its front matter and its body
+fn split_front_matter(text: &str) -> Option<(&str, &str)> {
+    let rest = text.strip_prefix(DELIMITER)?;
+    let rest = rest
+        .strip_prefix("\r\n")
+        .or_else(|| rest.strip_prefix('\n'))?;
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == DELIMITER {
+            return Some((&rest[..offset], &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+fn key(name: &str) -> Value {
+    Value::String(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_markdown, to_markdown};
+    use eingang::models::{Idable, Note, Task, TaskStatus};
+
+    #[test]
+    fn note_roundtrip() {
+        let content = "# Heading\n\nSome *content* with \"quotes\".\n".to_string();
+        let note = Note::with_title(content.clone(), "title".to_string());
+        let text = to_markdown(&note).unwrap();
+        assert!(text.starts_with("---\ntitle: title\n"));
+        assert!(text.ends_with(&content));
+
+        let read: Note = from_markdown(&text).unwrap();
+        assert_eq!(read.title, note.title);
+        assert_eq!(read.content, content);
+        assert_eq!(read.get_uuid(), note.get_uuid());
+    }
+
+    #[test]
+    fn task_roundtrip() {
+        let task = Task::with_title_and_status(
+            "content".to_string(),
+            "title".to_string(),
+            TaskStatus::Waiting,
+        );
+        let text = to_markdown(&task).unwrap();
+        assert!(text.contains("status: waiting\n"));
+
+        let read: Task = from_markdown(&text).unwrap();
+        assert_eq!(read.status, TaskStatus::Waiting);
+        assert_eq!(read.get_uuid(), task.get_uuid());
+    }
+
+    #[test]
+    fn missing_front_matter() {
+        assert!(from_markdown::<Note>("Just some text").is_err());
+        assert!(from_markdown::<Note>("---\ntitle: open\n").is_err());
+    }
+}
@@ -0,0 +1,130 @@
+//! Storage backend keeping all elements in memory.
+//!
+//! Nothing is persisted, which makes it a good fit for tests and throwaway
+//! sessions.
+use super::{EingangError, Result, Storage};
+use eingang::models::{Idable, Note, Task, Thread};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use uuid::Uuid;
+
+/// Storage of all elements in hash maps indexed by their UUID
+#[derive(Default)]
+pub struct MemoryStorage {
+    notes: RwLock<HashMap<Uuid, Note>>,
+    tasks: RwLock<HashMap<Uuid, Task>>,
+    threads: RwLock<HashMap<Uuid, Thread>>,
+    note_revisions: RwLock<HashMap<Uuid, Vec<Note>>>,
+    task_revisions: RwLock<HashMap<Uuid, Vec<Task>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+fn get<T: Clone>(map: &RwLock<HashMap<Uuid, T>>, uuid: &Uuid) -> Result<T> {
+    map.read()
+        .unwrap()
+        .get(uuid)
+        .cloned()
+        .ok_or_else(|| EingangError::NotFound(format!("UUID {}", uuid)))
+}
+
+fn list<T: Clone>(map: &RwLock<HashMap<Uuid, T>>) -> Result<Vec<T>> {
+    Ok(map.read().unwrap().values().cloned().collect())
+}
+
+fn save<T: Clone + Idable>(map: &RwLock<HashMap<Uuid, T>>, element: &T) -> Result<()> {
+    map.write()
+        .unwrap()
+        .insert(element.get_uuid(), element.clone());
+    Ok(())
+}
+
+/// Save the element and keep its previous version as revision
+fn save_revision<T: Clone + Idable>(
+    map: &RwLock<HashMap<Uuid, T>>,
+    revisions: &RwLock<HashMap<Uuid, Vec<T>>>,
+    element: &T,
+) -> Result<()> {
+    let uuid = element.get_uuid();
+    if let Some(previous) = map.write().unwrap().insert(uuid, element.clone()) {
+        revisions
+            .write()
+            .unwrap()
+            .entry(uuid)
+            .or_default()
+            .push(previous);
+    }
+    Ok(())
+}
+
+fn list_revisions<T: Clone>(revisions: &RwLock<HashMap<Uuid, Vec<T>>>, uuid: &Uuid) -> Vec<T> {
+    revisions
+        .read()
+        .unwrap()
+        .get(uuid)
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn delete<T>(map: &RwLock<HashMap<Uuid, T>>, uuid: &Uuid) -> Result<()> {
+    match map.write().unwrap().remove(uuid) {
+        Some(_) => Ok(()),
+        None => Err(EingangError::NotFound(format!("UUID {}", uuid))),
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get_note(&self, uuid: &Uuid) -> Result<Note> {
+        get(&self.notes, uuid)
+    }
+    fn list_notes(&self) -> Result<Vec<Note>> {
+        list(&self.notes)
+    }
+    fn save_note(&self, note: &Note) -> Result<()> {
+        save_revision(&self.notes, &self.note_revisions, note)
+    }
+    fn delete_note(&self, uuid: &Uuid) -> Result<()> {
+        delete(&self.notes, uuid)?;
+        self.note_revisions.write().unwrap().remove(uuid);
+        Ok(())
+    }
+
+    fn get_task(&self, uuid: &Uuid) -> Result<Task> {
+        get(&self.tasks, uuid)
+    }
+    fn list_tasks(&self) -> Result<Vec<Task>> {
+        list(&self.tasks)
+    }
+    fn save_task(&self, task: &Task) -> Result<()> {
+        save_revision(&self.tasks, &self.task_revisions, task)
+    }
+    fn delete_task(&self, uuid: &Uuid) -> Result<()> {
+        delete(&self.tasks, uuid)?;
+        self.task_revisions.write().unwrap().remove(uuid);
+        Ok(())
+    }
+
+    fn note_revisions(&self, uuid: &Uuid) -> Result<Vec<Note>> {
+        Ok(list_revisions(&self.note_revisions, uuid))
+    }
+    fn task_revisions(&self, uuid: &Uuid) -> Result<Vec<Task>> {
+        Ok(list_revisions(&self.task_revisions, uuid))
+    }
+
+    fn get_thread(&self, uuid: &Uuid) -> Result<Thread> {
+        get(&self.threads, uuid)
+    }
+    fn list_threads(&self) -> Result<Vec<Thread>> {
+        list(&self.threads)
+    }
+    fn save_thread(&self, thread: &Thread) -> Result<()> {
+        save(&self.threads, thread)
+    }
+    fn delete_thread(&self, uuid: &Uuid) -> Result<()> {
+        delete(&self.threads, uuid)
+    }
+}
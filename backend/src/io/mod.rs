@@ -0,0 +1,211 @@
+//! Storage of all content types.
+//!
+//! The [`Storage`] trait abstracts over the actual backend. Route handlers
+//! get the configured backend via `web::Data<dyn Storage>`, so new backends
+//! can be added without touching the routes:
+//!
+//! - [`FolderStorage`]: One file per element in a folder per content type
+//! - [`MemoryStorage`]: Everything kept in memory, e.g. for tests
+//! - [`SqliteStorage`]: A single SQLite database with indexed columns
+//!
+//! The opened backend is wrapped in an [`IndexedStorage`], which keeps the
+//! in-memory indexes (the [`LinkIndex`] and the [`SearchIndex`]) up to date.
+//!
+//! All functions return an [`EingangError`] on failure, which is mapped to a
+//! proper HTTP response with an `ErrorResponse` body.
+//!
+//! Elements which cannot be parsed are never fatal for listing: every backend
+//! moves them into quarantine, logs a warning and skips them. The quarantined
+//! elements can be inspected via [`Storage::list_quarantined`].
+use crate::DATABASE;
+use eingang::models::eingang::Timestamp;
+use eingang::models::{Note, Quarantined, Task, TaskStatus, Thread};
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub mod error;
+pub mod folder;
+pub mod fsck;
+pub mod indexed;
+pub mod links;
+pub mod markdown;
+pub mod memory;
+pub mod query;
+pub mod search;
+pub mod sqlite;
+
+pub use error::{parse_uuid, EingangError, Result};
+pub use folder::FolderStorage;
+pub use indexed::IndexedStorage;
+pub use links::LinkIndex;
+pub use memory::MemoryStorage;
+pub use search::SearchIndex;
+pub use sqlite::SqliteStorage;
+
+/// Available storage backends which can be selected at startup
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    #[serde(alias = "files")]
+    Folder,
+    #[serde(alias = "database")]
+    Sqlite,
+    Memory,
+}
+
+impl std::str::FromStr for Backend {
+    type Err = std::io::Error;
+    fn from_str(s: &str) -> std::io::Result<Self> {
+        match s.to_lowercase().as_str() {
+            "folder" | "files" => Ok(Backend::Folder),
+            "sqlite" | "database" => Ok(Backend::Sqlite),
+            "memory" => Ok(Backend::Memory),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Unknown storage backend '{}'", s),
+            )),
+        }
+    }
+}
+
+impl Backend {
+    /// Open the storage backend with all data below the `base` folder. The
+    /// folders are created if they do not exist yet.
+    pub fn open(self, base: &Path) -> Result<Arc<dyn Storage>> {
+        std::fs::create_dir_all(base)?;
+        let store: Arc<dyn Storage> = match self {
+            Backend::Folder => Arc::new(FolderStorage::create(base)?),
+            Backend::Sqlite => Arc::new(SqliteStorage::open(base.join(DATABASE))?),
+            Backend::Memory => Arc::new(MemoryStorage::new()),
+        };
+        Ok(store)
+    }
+}
+
+/// Backend for getting, listing, saving and deleting all content types
+pub trait Storage: Send + Sync {
+    fn get_note(&self, uuid: &Uuid) -> Result<Note>;
+    fn list_notes(&self) -> Result<Vec<Note>>;
+    fn save_note(&self, note: &Note) -> Result<()>;
+    fn delete_note(&self, uuid: &Uuid) -> Result<()>;
+
+    fn get_task(&self, uuid: &Uuid) -> Result<Task>;
+    fn list_tasks(&self) -> Result<Vec<Task>>;
+    fn save_task(&self, task: &Task) -> Result<()>;
+    fn delete_task(&self, uuid: &Uuid) -> Result<()>;
+
+    /// Return all earlier revisions of a note, oldest first. Every save keeps
+    /// the previous version as revision.
+    fn note_revisions(&self, uuid: &Uuid) -> Result<Vec<Note>>;
+    /// Return all earlier revisions of a task, oldest first
+    fn task_revisions(&self, uuid: &Uuid) -> Result<Vec<Task>>;
+
+    fn get_thread(&self, uuid: &Uuid) -> Result<Thread>;
+    fn list_threads(&self) -> Result<Vec<Thread>>;
+    fn save_thread(&self, thread: &Thread) -> Result<()>;
+    fn delete_thread(&self, uuid: &Uuid) -> Result<()>;
+
+    /// Return all notes last modified between `after` and `before`
+    fn list_notes_between(
+        &self,
+        before: Option<Timestamp>,
+        after: Option<Timestamp>,
+    ) -> Result<Vec<Note>> {
+        Ok(filter_notes(self.list_notes()?, before, after))
+    }
+    /// Return all tasks last modified between `after` and `before`
+    fn list_tasks_between(
+        &self,
+        before: Option<Timestamp>,
+        after: Option<Timestamp>,
+    ) -> Result<Vec<Task>> {
+        Ok(filter_tasks(self.list_tasks()?, before, after))
+    }
+    /// Return all threads last modified between `after` and `before`
+    fn list_threads_between(
+        &self,
+        before: Option<Timestamp>,
+        after: Option<Timestamp>,
+    ) -> Result<Vec<Thread>> {
+        Ok(filter_threads(self.list_threads()?, before, after))
+    }
+    /// Return all tasks with a certain status
+    fn list_tasks_with_status(&self, status: TaskStatus) -> Result<Vec<Task>> {
+        let tasks = self.list_tasks()?;
+        Ok(tasks.into_iter().filter(|t| t.status == status).collect())
+    }
+    /// Return all elements which were moved to quarantine, because they
+    /// could not be read
+    fn list_quarantined(&self) -> Result<Vec<Quarantined>> {
+        Ok(Vec::new())
+    }
+}
+
+pub fn filter_notes(
+    notes: Vec<Note>,
+    before: Option<Timestamp>,
+    after: Option<Timestamp>,
+) -> Vec<Note> {
+    if before.is_some() && after.is_some() {
+        notes
+            .into_iter()
+            .filter(|f| *f > after.unwrap())
+            .filter(|f| *f < before.unwrap())
+            .collect()
+    } else if before.is_some() {
+        notes.into_iter().filter(|f| *f < before.unwrap()).collect()
+    } else if after.is_some() {
+        notes.into_iter().filter(|f| *f > after.unwrap()).collect()
+    } else {
+        notes
+    }
+}
+
+pub fn filter_tasks(
+    tasks: Vec<Task>,
+    before: Option<Timestamp>,
+    after: Option<Timestamp>,
+) -> Vec<Task> {
+    if before.is_some() && after.is_some() {
+        tasks
+            .into_iter()
+            .filter(|f| *f > after.unwrap())
+            .filter(|f| *f < before.unwrap())
+            .collect()
+    } else if before.is_some() {
+        tasks.into_iter().filter(|f| *f < before.unwrap()).collect()
+    } else if after.is_some() {
+        tasks.into_iter().filter(|f| *f > after.unwrap()).collect()
+    } else {
+        tasks
+    }
+}
+
+pub fn filter_threads(
+    threads: Vec<Thread>,
+    before: Option<Timestamp>,
+    after: Option<Timestamp>,
+) -> Vec<Thread> {
+    if before.is_some() && after.is_some() {
+        threads
+            .into_iter()
+            .filter(|f| *f > after.unwrap())
+            .filter(|f| *f < before.unwrap())
+            .collect()
+    } else if before.is_some() {
+        threads
+            .into_iter()
+            .filter(|f| *f < before.unwrap())
+            .collect()
+    } else if after.is_some() {
+        threads
+            .into_iter()
+            .filter(|f| *f > after.unwrap())
+            .collect()
+    } else {
+        threads
+    }
+}
@@ -0,0 +1,128 @@
+//! Evaluation of a [`Query`] against any storage.
+//!
+//! Only the content types the query can match are loaded. Terms `after` and
+//! `before` narrow the loaded elements via the `list_*_between` functions of
+//! the storage, all other terms are checked element by element.
+//!
+//! Threads with a saved search (see [`Thread::query`]) get their items from
+//! [`resolve_search`] whenever they are read.
+use super::{Result, Storage};
+use eingang::models::eingang::Timestamp;
+use eingang::models::ThreadItem;
+use eingang::models::{Condition, ElementKind, Idable, JournalResponse, Query, Thread};
+
+/// Return all elements matching the query, most recently modified first
+pub fn evaluate(store: &dyn Storage, query: &Query) -> Result<Vec<JournalResponse>> {
+    let (before, after) = bounds(query);
+    let mut elements = Vec::new();
+    for kind in query.kinds() {
+        match kind {
+            ElementKind::Note => elements.extend(
+                store
+                    .list_notes_between(before, after)?
+                    .into_iter()
+                    .filter(|n| query.matches(n))
+                    .map(JournalResponse::Note),
+            ),
+            ElementKind::Task => elements.extend(
+                store
+                    .list_tasks_between(before, after)?
+                    .into_iter()
+                    .filter(|t| query.matches(t))
+                    .map(JournalResponse::Task),
+            ),
+            ElementKind::Thread => elements.extend(
+                store
+                    .list_threads_between(before, after)?
+                    .into_iter()
+                    .filter(|t| query.matches(t))
+                    .map(JournalResponse::Thread),
+            ),
+        }
+    }
+    elements.sort_by_key(|e| std::cmp::Reverse(last_modified(e)));
+    Ok(elements)
+}
+
+/// Replace the items of a saved search with the notes and tasks matching its
+/// query. Threads are never part of the results, so saved searches cannot
+/// contain themselves or form cycles.
+pub fn resolve_search(store: &dyn Storage, thread: &mut Thread) -> Result<()> {
+    let query = match &thread.query {
+        Some(query) => query,
+        None => return Ok(()),
+    };
+    thread.items = evaluate(store, query)?
+        .into_iter()
+        .filter_map(|e| match e {
+            JournalResponse::Note(n) => Some(ThreadItem::Note(n.get_uuid())),
+            JournalResponse::Task(t) => Some(ThreadItem::Task(t.get_uuid())),
+            JournalResponse::Thread(_) => None,
+        })
+        .collect();
+    Ok(())
+}
+
+/// Tightest range of modification dates required by the query
+fn bounds(query: &Query) -> (Option<Timestamp>, Option<Timestamp>) {
+    let mut before: Option<Timestamp> = None;
+    let mut after: Option<Timestamp> = None;
+    for term in query.terms.iter().filter(|t| !t.negated) {
+        match &term.condition {
+            Condition::Before(b) => before = before.min(Some(*b)).or(Some(*b)),
+            Condition::Untouched(p) => {
+                let b = p.to_timestamp();
+                before = before.min(Some(b)).or(Some(b))
+            }
+            Condition::After(a) => after = after.max(Some(*a)),
+            Condition::During(p) => after = after.max(Some(p.to_timestamp())),
+            _ => {}
+        }
+    }
+    (before, after)
+}
+
+fn last_modified(element: &JournalResponse) -> Timestamp {
+    match element {
+        JournalResponse::Note(n) => n.last_modified(),
+        JournalResponse::Task(t) => t.last_modified(),
+        JournalResponse::Thread(t) => t.last_modified(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::evaluate;
+    use crate::io::{MemoryStorage, SqliteStorage, Storage};
+    use eingang::models::{Idable, JournalResponse, Note, Task, TaskStatus};
+
+    #[test]
+    fn same_results_for_all_storages() {
+        let memory = MemoryStorage::new();
+        let sqlite = SqliteStorage::in_memory().unwrap();
+        let open = Task::new("Pay the invoice".to_string());
+        let draft = Task::with_title("invoice".to_string(), "Draft".to_string());
+        let closed =
+            Task::with_title_and_status("invoice".to_string(), String::new(), TaskStatus::Closed);
+        let note = Note::new("Invoice template".to_string());
+        for store in &[&memory as &dyn Storage, &sqlite] {
+            store.save_task(&open).unwrap();
+            store.save_task(&draft).unwrap();
+            store.save_task(&closed).unwrap();
+            store.save_note(&note).unwrap();
+        }
+
+        let query = "type:task status:open after:2000-01-01 invoice -draft"
+            .parse()
+            .unwrap();
+        for store in &[&memory as &dyn Storage, &sqlite] {
+            let found = evaluate(*store, &query).unwrap();
+            assert_eq!(found.len(), 1);
+            assert!(
+                matches!(&found[0], JournalResponse::Task(t) if t.get_uuid() == open.get_uuid())
+            );
+        }
+        let found = evaluate(&memory, &"invoice".parse().unwrap()).unwrap();
+        assert_eq!(found.len(), 4);
+    }
+}
@@ -0,0 +1,378 @@
+//! Full-text index of the titles and contents of notes and tasks.
+//!
+//! Text is split into words at every character which is neither a letter nor
+//! a digit. Words are lower-cased and reduced to their stem with the English
+//! Snowball stemmer, so `planning` also finds `planned`. The index keeps the
+//! position of every word, which allows phrase queries like `"weekly review"`.
+//!
+//! Hits have to match all words and phrases of a query. They are ranked with
+//! a BM25-like score, where matches in the title weigh more than matches in
+//! the content.
+//!
+//! Like the [`LinkIndex`], the index is built from the storage at startup and
+//! kept up to date by [`IndexedStorage`] on every save and delete.
+//!
+//! [`LinkIndex`]: super::LinkIndex
+//! [`IndexedStorage`]: super::IndexedStorage
+use super::{Result, Storage};
+use eingang::models::{Idable, SearchHit};
+use rust_stemmers::{Algorithm, Stemmer};
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+use uuid::Uuid;
+
+/// Weight of a match in the title compared to a match in the content
+const TITLE_WEIGHT: f64 = 2.0;
+/// Saturation of repeated matches in the same element (`k1` of BM25)
+const SATURATION: f64 = 1.2;
+/// Number of words shown before the first match in a snippet
+const SNIPPET_CONTEXT: usize = 6;
+/// Maximal number of words in a snippet
+const SNIPPET_WORDS: usize = 24;
+const HIGHLIGHT_START: &str = "<mark>";
+const HIGHLIGHT_END: &str = "</mark>";
+
+/// Inverted index of all notes and tasks
+pub struct SearchIndex {
+    stemmer: Stemmer,
+    inner: RwLock<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    elements: HashMap<Uuid, Entry>,
+    /// Positions of every stemmed word by element
+    postings: HashMap<String, HashMap<Uuid, Vec<Position>>>,
+}
+
+struct Entry {
+    kind: &'static str,
+    title: String,
+    content: String,
+    terms: HashSet<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Field {
+    Title,
+    Content,
+}
+
+/// Place of a word in an element, counted in words
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Position {
+    field: Field,
+    index: usize,
+}
+
+/// A single word of a text with its byte range
+struct Token {
+    term: String,
+    start: usize,
+    end: usize,
+}
+
+impl Default for SearchIndex {
+    fn default() -> Self {
+        SearchIndex {
+            stemmer: Stemmer::create(Algorithm::English),
+            inner: Default::default(),
+        }
+    }
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    /// Build the index from all notes and tasks in the storage
+    pub fn build(store: &dyn Storage) -> Result<Self> {
+        let index = SearchIndex::new();
+        for note in store.list_notes()? {
+            index.update(note.get_uuid(), "Note", &note.title, &note.content);
+        }
+        for task in store.list_tasks()? {
+            index.update(task.get_uuid(), "Task", &task.title, &task.content);
+        }
+        Ok(index)
+    }
+    /// Add or replace the element `uuid` in the index
+    pub fn update(&self, uuid: Uuid, kind: &'static str, title: &str, content: &str) {
+        let mut terms = HashSet::new();
+        let mut positions: HashMap<String, Vec<Position>> = HashMap::new();
+        for (field, text) in &[(Field::Title, title), (Field::Content, content)] {
+            for (index, token) in self.tokenize(text).into_iter().enumerate() {
+                let field = *field;
+                terms.insert(token.term.clone());
+                positions
+                    .entry(token.term)
+                    .or_default()
+                    .push(Position { field, index });
+            }
+        }
+
+        let mut inner = self.inner.write().unwrap();
+        inner.remove(&uuid);
+        for (term, p) in positions {
+            inner.postings.entry(term).or_default().insert(uuid, p);
+        }
+        let entry = Entry {
+            kind,
+            title: title.to_string(),
+            content: content.to_string(),
+            terms,
+        };
+        inner.elements.insert(uuid, entry);
+    }
+    /// Remove the element `uuid` from the index
+    pub fn remove(&self, uuid: &Uuid) {
+        self.inner.write().unwrap().remove(uuid);
+    }
+    /// Return the best `limit` elements matching all words and phrases of
+    /// the query
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let clauses = self.parse_query(query);
+        if clauses.is_empty() {
+            return Vec::new();
+        }
+        let inner = self.inner.read().unwrap();
+        let total = inner.elements.len() as f64;
+        let mut candidates: Option<HashSet<Uuid>> = None;
+        let mut scores: HashMap<Uuid, f64> = HashMap::new();
+        let mut matched: HashMap<Uuid, HashSet<usize>> = HashMap::new();
+
+        for clause in &clauses {
+            let matches = inner.matches(clause);
+            let found: HashSet<Uuid> = matches.keys().copied().collect();
+            candidates = Some(match candidates {
+                Some(c) => c.intersection(&found).copied().collect(),
+                None => found,
+            });
+            let count = matches.len() as f64;
+            let idf = (1.0 + (total - count + 0.5) / (count + 0.5)).ln();
+            for (uuid, positions) in matches {
+                let frequency: f64 = positions
+                    .iter()
+                    .map(|p| match p.field {
+                        Field::Title => TITLE_WEIGHT,
+                        Field::Content => 1.0,
+                    })
+                    .sum();
+                *scores.entry(uuid).or_default() +=
+                    idf * frequency * (SATURATION + 1.0) / (frequency + SATURATION);
+                let words = matched.entry(uuid).or_default();
+                for p in positions.iter().filter(|p| p.field == Field::Content) {
+                    words.extend(p.index..p.index + clause.len());
+                }
+            }
+        }
+
+        let mut hits: Vec<SearchHit> = candidates
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|uuid| {
+                let entry = inner.elements.get(uuid)?;
+                Some(SearchHit {
+                    uuid: *uuid,
+                    kind: entry.kind.to_string(),
+                    title: entry.title.clone(),
+                    score: scores.get(uuid).copied().unwrap_or_default(),
+                    snippet: self.snippet(&entry.content, &matched[uuid]),
+                })
+            })
+            .collect();
+        hits.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.title.cmp(&b.title))
+                .then_with(|| a.uuid.cmp(&b.uuid))
+        });
+        hits.truncate(limit);
+        hits
+    }
+    fn tokenize(&self, text: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        let mut start = None;
+        let end = std::iter::once((text.len(), ' '));
+        for (i, c) in text.char_indices().chain(end) {
+            match (c.is_alphanumeric(), start) {
+                (true, None) => start = Some(i),
+                (false, Some(s)) => {
+                    let word = text[s..i].to_lowercase();
+                    tokens.push(Token {
+                        term: self.stemmer.stem(&word).into_owned(),
+                        start: s,
+                        end: i,
+                    });
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        tokens
+    }
+    /// Split the query into single words and phrases given in double quotes
+    fn parse_query(&self, query: &str) -> Vec<Vec<String>> {
+        let mut clauses = Vec::new();
+        for (i, part) in query.split('"').enumerate() {
+            let terms: Vec<String> = self.tokenize(part).into_iter().map(|t| t.term).collect();
+            if i % 2 == 1 {
+                if !terms.is_empty() {
+                    clauses.push(terms);
+                }
+            } else {
+                clauses.extend(terms.into_iter().map(|t| vec![t]));
+            }
+        }
+        clauses
+    }
+    /// Cut the content around the first matched word and highlight all
+    /// matched words. The content is HTML-escaped, so only the highlights are
+    /// markup.
+    fn snippet(&self, content: &str, matched: &HashSet<usize>) -> String {
+        let tokens = self.tokenize(content);
+        if tokens.is_empty() {
+            return String::new();
+        }
+        let first = matched.iter().min().copied().unwrap_or_default();
+        let start = first.saturating_sub(SNIPPET_CONTEXT);
+        let end = tokens.len().min(start + SNIPPET_WORDS);
+
+        let mut snippet = String::new();
+        if start > 0 {
+            snippet.push('…');
+        }
+        let mut last = tokens[start].start;
+        for (i, token) in tokens.iter().enumerate().take(end).skip(start) {
+            snippet.push_str(&escape(&content[last..token.start]));
+            let highlighted = matched.contains(&i);
+            if highlighted && (i == start || !matched.contains(&(i - 1))) {
+                snippet.push_str(HIGHLIGHT_START);
+            }
+            snippet.push_str(&escape(&content[token.start..token.end]));
+            if highlighted && (i + 1 == end || !matched.contains(&(i + 1))) {
+                snippet.push_str(HIGHLIGHT_END);
+            }
+            last = token.end;
+        }
+        if end < tokens.len() {
+            snippet.push('…');
+        } else {
+            snippet.push_str(&escape(content[last..].trim_end()));
+        }
+        snippet
+    }
+}
+
+/// Escape the characters with a special meaning in HTML
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl Inner {
+    fn remove(&mut self, uuid: &Uuid) {
+        if let Some(entry) = self.elements.remove(uuid) {
+            for term in entry.terms {
+                if let Some(elements) = self.postings.get_mut(&term) {
+                    elements.remove(uuid);
+                    if elements.is_empty() {
+                        self.postings.remove(&term);
+                    }
+                }
+            }
+        }
+    }
+    /// Return the positions of the first word of every occurrence of the
+    /// phrase `terms`, by element
+    fn matches(&self, terms: &[String]) -> HashMap<Uuid, Vec<Position>> {
+        let first = match self.postings.get(&terms[0]) {
+            Some(f) => f,
+            None => return HashMap::new(),
+        };
+        let mut matches = HashMap::new();
+        for (uuid, positions) in first {
+            let found: Vec<Position> = positions
+                .iter()
+                .copied()
+                .filter(|p| {
+                    terms[1..].iter().enumerate().all(|(offset, term)| {
+                        let next = Position {
+                            field: p.field,
+                            index: p.index + offset + 1,
+                        };
+                        self.postings
+                            .get(term)
+                            .and_then(|elements| elements.get(uuid))
+                            .is_some_and(|ps| ps.binary_search(&next).is_ok())
+                    })
+                })
+                .collect();
+            if !found.is_empty() {
+                matches.insert(*uuid, found);
+            }
+        }
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SearchIndex;
+    use uuid::Uuid;
+
+    #[test]
+    fn ranked_hits_with_stemming_and_phrases() {
+        let index = SearchIndex::new();
+        let (review, notes, other) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        index.update(
+            review,
+            "Note",
+            "Weekly review",
+            "Every Friday the weekly review of all open projects.",
+        );
+        index.update(notes, "Task", "Notes", "Review the notes of the week");
+        index.update(other, "Note", "Groceries", "Milk and bread");
+
+        let hits = index.search("reviewing", 10);
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].uuid, review);
+
+        let hits = index.search("\"weekly review\" projects", 10);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(
+            hits[0].snippet,
+            "Every Friday the <mark>weekly review</mark> of all open <mark>projects</mark>."
+        );
+
+        index.remove(&review);
+        index.update(other, "Note", "Groceries", "Review the shopping list");
+        let hits: Vec<Uuid> = index.search("review", 10).iter().map(|h| h.uuid).collect();
+        assert_eq!(hits.len(), 2);
+        assert!(!hits.contains(&review));
+        assert!(index.search("milk", 10).is_empty());
+    }
+
+    #[test]
+    fn snippets_escape_markup() {
+        let index = SearchIndex::new();
+        let uuid = Uuid::new_v4();
+        index.update(uuid, "Note", "Page", "Fix <script>alert(1)</script> & ship");
+        let hits = index.search("alert", 10);
+        assert_eq!(
+            hits[0].snippet,
+            "Fix &lt;script&gt;<mark>alert</mark>(1)&lt;/script&gt; &amp; ship"
+        );
+    }
+}
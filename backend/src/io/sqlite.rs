@@ -0,0 +1,457 @@
+//! Storage backend using a single SQLite database.
+//!
+//! Each content type has its own table. The element itself is saved as JSON
+//! in the `data` column, while the fields used for filtering (`status`,
+//! `last_modified`) are kept in separate, indexed columns. The membership of
+//! notes and tasks in threads is kept in the `thread_members` table.
+//!
+//! Before a note or task is overwritten, its previous `data` is copied to the
+//! `revisions` table, numbered from 1 (oldest) per element.
+//!
+//! Rows whose `data` cannot be parsed are moved to the `quarantine` table
+//! together with the parse error.
+use super::{EingangError, Result, Storage};
+use eingang::models::eingang::Timestamp;
+use eingang::models::{Idable, Note, Quarantined, Task, TaskStatus, Thread};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::Path;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS notes (
+        uuid TEXT PRIMARY KEY,
+        title TEXT NOT NULL,
+        created_on TEXT NOT NULL,
+        last_modified TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS notes_last_modified ON notes (last_modified);
+
+    CREATE TABLE IF NOT EXISTS tasks (
+        uuid TEXT PRIMARY KEY,
+        title TEXT NOT NULL,
+        status TEXT NOT NULL,
+        created_on TEXT NOT NULL,
+        last_modified TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS tasks_last_modified ON tasks (last_modified);
+    CREATE INDEX IF NOT EXISTS tasks_status ON tasks (status);
+
+    CREATE TABLE IF NOT EXISTS threads (
+        uuid TEXT PRIMARY KEY,
+        created_on TEXT NOT NULL,
+        last_modified TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS threads_last_modified ON threads (last_modified);
+
+    CREATE TABLE IF NOT EXISTS thread_members (
+        thread TEXT NOT NULL REFERENCES threads (uuid) ON DELETE CASCADE,
+        member TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        PRIMARY KEY (thread, kind, position)
+    );
+    CREATE INDEX IF NOT EXISTS thread_members_member ON thread_members (member);
+
+    CREATE TABLE IF NOT EXISTS quarantine (
+        kind TEXT NOT NULL,
+        uuid TEXT NOT NULL,
+        data TEXT NOT NULL,
+        error TEXT NOT NULL,
+        quarantined_on TEXT NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS revisions (
+        kind TEXT NOT NULL,
+        uuid TEXT NOT NULL,
+        revision INTEGER NOT NULL,
+        data TEXT NOT NULL,
+        PRIMARY KEY (kind, uuid, revision)
+    );
+";
+
+/// Storage of all elements in a SQLite database
+pub struct SqliteStorage {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    /// Open (or create) the database at `path`
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        SqliteStorage::with_connection(conn)
+    }
+    /// Create a database which only lives in memory
+    pub fn in_memory() -> Result<Self> {
+        let conn = Connection::open_in_memory()?;
+        SqliteStorage::with_connection(conn)
+    }
+    fn with_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(SqliteStorage {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn get<T: DeserializeOwned>(&self, table: &str, uuid: &Uuid) -> Result<T> {
+        let conn = self.conn.lock().unwrap();
+        let sql = format!("SELECT data FROM {} WHERE uuid = ?1", table);
+        let data: Option<String> = conn
+            .query_row(&sql, params![uuid.to_string()], |row| row.get(0))
+            .optional()?;
+        match data {
+            Some(d) => from_json(&d).map_err(|e| match e {
+                EingangError::Corrupt(error) => {
+                    match quarantine(&conn, table, &uuid.to_string(), &error) {
+                        Ok(()) => EingangError::Corrupt(error),
+                        Err(e) => e,
+                    }
+                }
+                e => e,
+            }),
+            None => Err(EingangError::NotFound(format!("UUID {}", uuid))),
+        }
+    }
+    /// Return all elements of `table` matching the `condition`
+    fn query<T: DeserializeOwned>(
+        &self,
+        table: &str,
+        condition: &str,
+        params: &[&dyn rusqlite::ToSql],
+    ) -> Result<Vec<T>> {
+        let conn = self.conn.lock().unwrap();
+        let sql = format!("SELECT uuid, data FROM {} {}", table, condition);
+        let rows = conn
+            .prepare(&sql)?
+            .query_map(params, |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<(String, String)>>>()?;
+        let mut elements = Vec::with_capacity(rows.len());
+        for (uuid, data) in rows {
+            match from_json(&data) {
+                Ok(element) => elements.push(element),
+                Err(EingangError::Corrupt(error)) => quarantine(&conn, table, &uuid, &error)?,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(elements)
+    }
+    fn between<T: DeserializeOwned>(
+        &self,
+        table: &str,
+        before: Option<Timestamp>,
+        after: Option<Timestamp>,
+    ) -> Result<Vec<T>> {
+        self.query(
+            table,
+            "WHERE (?1 IS NULL OR last_modified < ?1) AND (?2 IS NULL OR last_modified > ?2)",
+            &[&before.map(timestamp), &after.map(timestamp)],
+        )
+    }
+    /// Return all revisions of an element, oldest first
+    fn revisions<T: DeserializeOwned>(&self, table: &str, uuid: &Uuid) -> Result<Vec<T>> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn
+            .prepare("SELECT data FROM revisions WHERE kind = ?1 AND uuid = ?2 ORDER BY revision")?
+            .query_map(params![kind(table), uuid.to_string()], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        rows.iter().map(|data| from_json(data)).collect()
+    }
+    fn delete(&self, table: &str, uuid: &Uuid) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let sql = format!("DELETE FROM {} WHERE uuid = ?1", table);
+        if conn.execute(&sql, params![uuid.to_string()])? == 0 {
+            return Err(EingangError::NotFound(format!("UUID {}", uuid)));
+        }
+        conn.execute(
+            "DELETE FROM revisions WHERE kind = ?1 AND uuid = ?2",
+            params![kind(table), uuid.to_string()],
+        )?;
+        Ok(())
+    }
+}
+
+/// Copy the current row of an element to the revisions table, if it exists
+fn keep_revision(conn: &Connection, table: &str, uuid: &str) -> Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO revisions (kind, uuid, revision, data) \
+             SELECT ?1, uuid, \
+                 (SELECT COALESCE(MAX(revision), 0) + 1 FROM revisions \
+                  WHERE kind = ?1 AND uuid = ?2), \
+                 data \
+             FROM {} WHERE uuid = ?2",
+            table
+        ),
+        params![kind(table), uuid],
+    )?;
+    Ok(())
+}
+
+/// Move an unreadable row of `table` to the quarantine table
+fn quarantine(conn: &Connection, table: &str, uuid: &str, error: &str) -> Result<()> {
+    log::warn!("Moving {} {} to quarantine: {}", kind(table), uuid, error);
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        &format!(
+            "INSERT INTO quarantine (kind, uuid, data, error, quarantined_on) \
+             SELECT ?1, uuid, data, ?2, ?3 FROM {} WHERE uuid = ?4",
+            table
+        ),
+        params![kind(table), error, timestamp(chrono::Utc::now()), uuid],
+    )?;
+    tx.execute(
+        &format!("DELETE FROM {} WHERE uuid = ?1", table),
+        params![uuid],
+    )?;
+    Ok(tx.commit()?)
+}
+
+/// Name of the content type saved in `table`
+fn kind(table: &str) -> &'static str {
+    match table {
+        "notes" => "Note",
+        "tasks" => "Task",
+        _ => "Thread",
+    }
+}
+
+/// Timestamps are saved with a fixed width, so they can be compared as text
+fn timestamp(t: Timestamp) -> String {
+    t.to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
+}
+
+fn status(s: TaskStatus) -> String {
+    serde_json::to_value(s)
+        .ok()
+        .and_then(|v| v.as_str().map(String::from))
+        .unwrap_or_default()
+}
+
+fn to_json<T: Serialize>(element: &T) -> Result<String> {
+    Ok(serde_json::to_string(element)?)
+}
+
+fn from_json<T: DeserializeOwned>(data: &str) -> Result<T> {
+    Ok(serde_json::from_str(data)?)
+}
+
+impl Storage for SqliteStorage {
+    fn get_note(&self, uuid: &Uuid) -> Result<Note> {
+        self.get("notes", uuid)
+    }
+    fn list_notes(&self) -> Result<Vec<Note>> {
+        self.query("notes", "", &[])
+    }
+    fn save_note(&self, note: &Note) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+        keep_revision(&tx, "notes", &note.get_uuid().to_string())?;
+        tx.execute(
+            "INSERT OR REPLACE INTO notes (uuid, title, created_on, last_modified, data) \
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                note.get_uuid().to_string(),
+                note.title,
+                timestamp(note.created_on()),
+                timestamp(note.last_modified()),
+                to_json(note)?,
+            ],
+        )?;
+        Ok(tx.commit()?)
+    }
+    fn delete_note(&self, uuid: &Uuid) -> Result<()> {
+        self.delete("notes", uuid)
+    }
+
+    fn get_task(&self, uuid: &Uuid) -> Result<Task> {
+        self.get("tasks", uuid)
+    }
+    fn list_tasks(&self) -> Result<Vec<Task>> {
+        self.query("tasks", "", &[])
+    }
+    fn save_task(&self, task: &Task) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let tx = conn.unchecked_transaction()?;
+        keep_revision(&tx, "tasks", &task.get_uuid().to_string())?;
+        tx.execute(
+            "INSERT OR REPLACE INTO tasks (uuid, title, status, created_on, last_modified, data) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                task.get_uuid().to_string(),
+                task.title,
+                status(task.status),
+                timestamp(task.created_on()),
+                timestamp(task.last_modified()),
+                to_json(task)?,
+            ],
+        )?;
+        Ok(tx.commit()?)
+    }
+    fn delete_task(&self, uuid: &Uuid) -> Result<()> {
+        self.delete("tasks", uuid)
+    }
+
+    fn note_revisions(&self, uuid: &Uuid) -> Result<Vec<Note>> {
+        self.revisions("notes", uuid)
+    }
+    fn task_revisions(&self, uuid: &Uuid) -> Result<Vec<Task>> {
+        self.revisions("tasks", uuid)
+    }
+
+    fn get_thread(&self, uuid: &Uuid) -> Result<Thread> {
+        self.get("threads", uuid)
+    }
+    fn list_threads(&self) -> Result<Vec<Thread>> {
+        self.query("threads", "", &[])
+    }
+    fn save_thread(&self, thread: &Thread) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let uuid = thread.get_uuid().to_string();
+        tx.execute(
+            "INSERT OR REPLACE INTO threads (uuid, created_on, last_modified, data) \
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                uuid,
+                timestamp(thread.created_on()),
+                timestamp(thread.last_modified()),
+                to_json(thread)?,
+            ],
+        )?;
+        tx.execute(
+            "DELETE FROM thread_members WHERE thread = ?1",
+            params![uuid],
+        )?;
+        for (position, item) in thread.items.iter().enumerate() {
+            let kind = item.kind().to_lowercase();
+            tx.execute(
+                "INSERT INTO thread_members (thread, member, kind, position) \
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![uuid, item.uuid().to_string(), kind, position as i64],
+            )?;
+        }
+        Ok(tx.commit()?)
+    }
+    fn delete_thread(&self, uuid: &Uuid) -> Result<()> {
+        self.delete("threads", uuid)
+    }
+
+    fn list_notes_between(
+        &self,
+        before: Option<Timestamp>,
+        after: Option<Timestamp>,
+    ) -> Result<Vec<Note>> {
+        self.between("notes", before, after)
+    }
+    fn list_tasks_between(
+        &self,
+        before: Option<Timestamp>,
+        after: Option<Timestamp>,
+    ) -> Result<Vec<Task>> {
+        self.between("tasks", before, after)
+    }
+    fn list_threads_between(
+        &self,
+        before: Option<Timestamp>,
+        after: Option<Timestamp>,
+    ) -> Result<Vec<Thread>> {
+        self.between("threads", before, after)
+    }
+    fn list_tasks_with_status(&self, s: TaskStatus) -> Result<Vec<Task>> {
+        self.query("tasks", "WHERE status = ?1", &[&status(s)])
+    }
+    fn list_quarantined(&self) -> Result<Vec<Quarantined>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT kind, uuid, error, quarantined_on FROM quarantine")?;
+        let rows = stmt.query_map(params![], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?;
+        let mut entries = Vec::new();
+        for row in rows {
+            let (kind, uuid, error, on): (String, String, String, String) = row?;
+            let quarantined_on = chrono::DateTime::parse_from_rfc3339(&on)
+                .map_err(|e| EingangError::Corrupt(e.to_string()))?
+                .with_timezone(&chrono::Utc);
+            entries.push(Quarantined {
+                kind,
+                name: uuid,
+                error,
+                quarantined_on,
+            });
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SqliteStorage;
+    use crate::io::Storage;
+    use eingang::models::{Idable, Note, Task, TaskStatus, Thread};
+
+    #[test]
+    fn save_and_query_elements() {
+        let store = SqliteStorage::in_memory().unwrap();
+        let note = Note::with_title("content".to_string(), "title".to_string());
+        let task = Task::with_title_and_status(
+            "content".to_string(),
+            "title".to_string(),
+            TaskStatus::Waiting,
+        );
+        store.save_note(&note).unwrap();
+        store.save_task(&task).unwrap();
+        let thread = Thread::with_tasks_and_notes(vec![task.get_uuid()], vec![note.get_uuid()]);
+        store.save_thread(&thread).unwrap();
+
+        let read = store.get_note(&note.get_uuid()).unwrap();
+        assert_eq!(read.title, "title");
+        assert_eq!(
+            store
+                .list_tasks_with_status(TaskStatus::Waiting)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(store
+            .list_tasks_with_status(TaskStatus::Open)
+            .unwrap()
+            .is_empty());
+
+        let after = chrono::Utc::now() - chrono::Duration::days(1);
+        assert_eq!(
+            store.list_notes_between(None, Some(after)).unwrap().len(),
+            1
+        );
+        assert!(store
+            .list_notes_between(Some(after), None)
+            .unwrap()
+            .is_empty());
+
+        store.delete_thread(&thread.get_uuid()).unwrap();
+        assert!(store.get_thread(&thread.get_uuid()).is_err());
+    }
+
+    #[test]
+    fn corrupt_rows_are_quarantined() {
+        let store = SqliteStorage::in_memory().unwrap();
+        let note = Note::new("content".to_string());
+        store.save_note(&note).unwrap();
+        store
+            .conn
+            .lock()
+            .unwrap()
+            .execute("UPDATE notes SET data = '{'", rusqlite::params![])
+            .unwrap();
+
+        assert!(store.list_notes().unwrap().is_empty());
+        let quarantined = store.list_quarantined().unwrap();
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(quarantined[0].name, note.get_uuid().to_string());
+        assert!(store.get_note(&note.get_uuid()).is_err());
+    }
+}
@@ -2,18 +2,24 @@
 //! Library for the backend system of the `eingang` tool. Further, only used for
 //! declaration of modules and configuration settings.
 
-// Configuration
+// Configuration (default values, see `config` for runtime settings)
+pub const APP_NAME: &str = "eingang";
+pub const CONFIG_FILE: &str = "config.toml";
 pub const HOST: &str = "127.0.0.1";
-pub const PORT: &str = "8081";
-pub const STORAGE: &str = "/tmp/db.json";
+pub const PORT: u16 = 8081;
+pub const STORAGE: &str = "db.json";
 pub const FRONTEND_HOST: &str = "http://localhost";
 pub const FRONTEND_PORT: &str = "8080";
+pub const LOG_LEVEL: &str = "actix_web=info";
 
-pub const BASE_FOLDER: &str = "/tmp";
 pub const THREAD_FOLDER: &str = "threads";
 pub const NOTE_FOLDER: &str = "notes";
 pub const TASK_FOLDER: &str = "tasks";
+pub const DATABASE: &str = "eingang.sqlite";
+pub const QUARANTINE_FOLDER: &str = "quarantine";
+pub const REVISION_FOLDER: &str = "revisions";
 
 // Modules
+pub mod config;
 pub mod io;
 pub mod routes;
@@ -1,23 +1,62 @@
 //! Main application for backend service.
 use actix_cors::Cors;
 use actix_web::http::header;
-use actix_web::{middleware, App, HttpServer};
-use eingang_backend::routes::{config, journal, notes, tasks, threads};
-use eingang_backend::{FRONTEND_HOST, FRONTEND_PORT, HOST, PORT};
+use actix_web::{middleware, web, App, HttpServer};
+use eingang::models::Query;
+use eingang_backend::config::{cli, Config};
+use eingang_backend::io::{fsck, query, IndexedStorage, Storage};
+use eingang_backend::routes::threads;
+use eingang_backend::routes::{admin, config, find, journal, notes, search, tags, tasks};
+use std::sync::Arc;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let address = format!("{}:{}", HOST, PORT);
-    let frontend = format!("{}:{}", FRONTEND_HOST, FRONTEND_PORT);
-    std::env::set_var("RUST_LOG", "actix_web=info");
-    env_logger::init();
+    let matches = cli().get_matches();
+    let settings = Config::load(&matches)?;
+    env_logger::Builder::new()
+        .parse_filters(&settings.log_level)
+        .init();
+    let store = settings.storage.open(&settings.data_dir)?;
+
+    if let Some(fsck_matches) = matches.subcommand_matches("fsck") {
+        let report = if fsck_matches.is_present("repair") {
+            fsck::repair(&*store)?
+        } else {
+            fsck::check(&*store)?
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    if let Some(find_matches) = matches.subcommand_matches("find") {
+        let query: Vec<&str> = find_matches
+            .values_of("query")
+            .unwrap_or_default()
+            .collect();
+        let query = Query::parse_in(&query.join(" "), &settings.time_zone)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let elements = query::evaluate(&*store, &query)?;
+        println!("{}", serde_json::to_string_pretty(&elements)?);
+        return Ok(());
+    }
+    let store = IndexedStorage::build(store)?;
+    let links = web::Data::from(store.links());
+    let index = web::Data::from(store.search());
+    let store: Arc<dyn Storage> = Arc::new(store);
+    let address = settings.address();
+    let settings = web::Data::new(settings);
 
     HttpServer::new(move || {
+        let cors = settings
+            .cors_origins
+            .iter()
+            .fold(Cors::new(), |cors, origin| cors.allowed_origin(origin));
         App::new()
+            .app_data(web::Data::from(store.clone()))
+            .app_data(settings.clone())
+            .app_data(links.clone())
+            .app_data(index.clone())
             .wrap(
-                Cors::new()
-                    .allowed_origin(&frontend)
-                    .allowed_methods(vec!["GET", "POST", "DELETE", "PATCH"])
+                cors.allowed_methods(vec!["GET", "POST", "DELETE", "PATCH"])
                     .allowed_headers(vec![header::AUTHORIZATION, header::ACCEPT])
                     .allowed_header(header::CONTENT_TYPE)
                     .max_age(3600)
@@ -30,6 +69,10 @@ async fn main() -> std::io::Result<()> {
             .configure(journal::config)
             .configure(tasks::config)
             .configure(threads::config)
+            .configure(tags::config)
+            .configure(search::config)
+            .configure(find::config)
+            .configure(admin::config)
     })
     .bind(&address)?
     .run()
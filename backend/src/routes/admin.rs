@@ -0,0 +1,35 @@
+//! # Routes: Administration
+//!
+//! Routes for inspecting and repairing the storage.
+//!
+//! ## Routes
+//! - `/admin/quarantine`: Return all elements moved to quarantine, because
+//!   they could not be read
+//! - `/admin/fsck`: Check the references between threads and their elements
+//! - `/admin/fsck/repair`: Remove dangling and duplicate references from threads
+use super::{EingangResponse, EingangVecResponse};
+use crate::io::{fsck, Storage};
+use actix_web::{web, HttpRequest};
+use eingang::models::{FsckReport, Quarantined};
+
+/// Configure routes for administration
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/admin/quarantine").route(web::get().to(get_quarantined)));
+    cfg.service(web::resource("/admin/fsck").route(web::get().to(check)));
+    cfg.service(web::resource("/admin/fsck/repair").route(web::post().to(repair)));
+}
+
+async fn get_quarantined(
+    _: HttpRequest,
+    store: web::Data<dyn Storage>,
+) -> EingangVecResponse<Quarantined> {
+    Ok(web::Json(store.list_quarantined()?))
+}
+
+async fn check(_: HttpRequest, store: web::Data<dyn Storage>) -> EingangResponse<FsckReport> {
+    Ok(web::Json(fsck::check(&**store)?))
+}
+
+async fn repair(_: HttpRequest, store: web::Data<dyn Storage>) -> EingangResponse<FsckReport> {
+    Ok(web::Json(fsck::repair(&**store)?))
+}
@@ -0,0 +1,108 @@
+//! # Routes: Find
+//!
+//! ## Routes
+//! - `/find?q=...`: Return all notes, tasks and threads matching the query,
+//!   most recently modified first, e.g.
+//!   `q=type:task status:open after:2020-10-01 invoice -draft`. See
+//!   [`eingang::models::eingang::query`] for the syntax. Dates without offset
+//!   are local to the configured `time_zone`.
+use super::EingangVecResponse;
+use crate::config::Config;
+use crate::io::{query, EingangError, Storage};
+use actix_web::{web, HttpRequest};
+use eingang::models::{JournalResponse, Query, QueryRequest};
+
+/// Configure routes for Find
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/find").route(web::get().to(find)));
+}
+
+async fn find(
+    _: HttpRequest,
+    q: web::Query<QueryRequest>,
+    store: web::Data<dyn Storage>,
+    config: web::Data<Config>,
+) -> EingangVecResponse<JournalResponse> {
+    let query = match &q.q {
+        Some(query) => {
+            Query::parse_in(query, &config.time_zone).map_err(EingangError::BadRequest)?
+        }
+        None => {
+            return Err(EingangError::BadRequest(
+                "Query parameter 'q' is missing".into(),
+            ))
+        }
+    };
+    Ok(web::Json(query::evaluate(&**store, &query)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::io::{MemoryStorage, Storage};
+    use actix_web::{test, web, App};
+    use eingang::models::{Idable, JournalFilter, JournalQuery, JournalResponse, Note};
+    use std::sync::Arc;
+
+    /// Note last modified at the moment
+    fn note(content: &str, modified: &str) -> Note {
+        let mut value = serde_json::to_value(Note::new(content.to_string())).unwrap();
+        value["meta"]["last_modified"] = modified.into();
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn uuids(found: &[JournalResponse]) -> Vec<uuid::Uuid> {
+        let mut uuids: Vec<_> = found
+            .iter()
+            .map(|element| match element {
+                JournalResponse::Note(n) => n.get_uuid(),
+                JournalResponse::Task(t) => t.get_uuid(),
+                JournalResponse::Thread(t) => t.get_uuid(),
+            })
+            .collect();
+        uuids.sort();
+        uuids
+    }
+
+    #[actix_rt::test]
+    async fn find_and_journal_agree_on_dates() {
+        let store: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        // The day before, the first hour of the day and the day after in Berlin
+        for (content, modified) in &[
+            ("before", "2020-09-30T21:00:00Z"),
+            ("during", "2020-09-30T23:00:00Z"),
+            ("after", "2020-10-02T12:00:00Z"),
+        ] {
+            store.save_note(&note(content, modified)).unwrap();
+        }
+        let config = Config {
+            time_zone: chrono_tz::Europe::Berlin,
+            ..Default::default()
+        };
+        let mut app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store.clone()))
+                .app_data(web::Data::new(config))
+                .configure(super::config)
+                .configure(crate::routes::journal::config),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/find?q=type:note%20after:2020-10-01")
+            .to_request();
+        let found: Vec<JournalResponse> = test::read_response_json(&mut app, req).await;
+        let query = JournalQuery {
+            after: Some("2020-10-01".to_string()),
+            filter: Some(JournalFilter::Notes),
+            ..Default::default()
+        };
+        let req = test::TestRequest::get()
+            .uri("/journal")
+            .set_json(&query)
+            .to_request();
+        let journal: Vec<JournalResponse> = test::read_response_json(&mut app, req).await;
+        assert_eq!(found.len(), 2);
+        assert_eq!(uuids(&found), uuids(&journal));
+    }
+}
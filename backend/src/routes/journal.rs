@@ -5,19 +5,31 @@
 //! - `/journal`: No outout without query parameters.
 //!   - `after=moment`:       Return all data last modified after certain date  (either/or `untouched`)
 //!   - `before=moment`:      Return all data last modified before certain date
-//!   - `during=period`:      Return all data last modified during the last period (either/or `untouched`, `moments`)
+//!   - `during=period`:      Return all data last modified during the last period or range (either/or `untouched`, `moments`)
 //!   - `untouched=period`:   Return all data last modified before the last period
 //!   - `kind=[notes|tasks]`: Return only data on Notes or Tasks (default: Threads)
+//!   - `tag=tag`:            Return only notes and tasks with the tag or one of its descendants
+//!   - `archived=true`:      Return archived threads as well
+//!
+//! Moments are RFC 3339 timestamps, dates with time `YYYY-MM-DD HH:MM[:SS]` or
+//! dates `YYYY-MM-DD`. Moments without offset are local to the configured
+//! `time_zone`, so `after=2020-10-01` means after midnight at the start of
+//! that day there. `after`, `before` and `during` also accept ranges in words
+//! like `today`, `last month`, `2020-W42`, `3 days ago` or `since monday` (see
+//! [`eingang::models::Range`]). Both dates and ranges count from their start:
+//! `after=yesterday` includes yesterday like `after` with yesterday's date,
+//! `before` excludes it. Invalid moments are rejected (400). Periods
+//! subtract months and years on the calendar, e.g. one month before March
+//! 31st is the end of February.
 //!
 //! The actual request for a period can be translated to the same function.
 //! Therefore only the cases `after`, `before`, and `after` and `before` needs
 //! to be implemented.
-use actix_web::{web, HttpRequest, HttpResponse};
-use eingang::models::{JournalFilter, JournalQuery, JournalResponse};
-use crate::io::{get_all_notes, filter_notes};
-use crate::io::{get_all_tasks, filter_tasks};
-use crate::io::{get_all_threads, filter_threads};
 use super::EingangVecResponse;
+use crate::config::Config;
+use crate::io::{EingangError, Result, Storage};
+use actix_web::{web, HttpRequest};
+use eingang::models::{filter_by_tag, Bounds, JournalFilter, JournalQuery, JournalResponse};
 
 /// Configure routes for Journal view
 pub fn config(cfg: &mut web::ServiceConfig) {
@@ -28,85 +40,126 @@ pub fn config(cfg: &mut web::ServiceConfig) {
 async fn journal(
     _: HttpRequest,
     d: web::Json<JournalQuery>,
+    store: web::Data<dyn Storage>,
+    config: web::Data<Config>,
 ) -> EingangVecResponse<JournalResponse> {
     let data = d.into_inner();
     if data.during.is_some() && data.untouched.is_some() {
-        return Err(HttpResponse::BadRequest().json("Either during OR untouched"));
+        return Err(EingangError::BadRequest(
+            "Either during OR untouched".into(),
+        ));
     }
     if (data.during.is_some() || data.untouched.is_some())
         && (data.before.is_some() || data.after.is_some())
     {
-        return Err(HttpResponse::BadRequest().json("Either time period OR moment"));
+        return Err(EingangError::BadRequest(
+            "Either time period OR moment".into(),
+        ));
     }
+    let now = chrono::Utc::now().with_timezone(&config.time_zone);
+    let bounds = data.bounds(&now).map_err(EingangError::BadRequest)?;
     let filter = data.filter.unwrap_or_default();
-    let result = filtering(filter, &data);
+    let result = filtering(filter, &data, bounds, &**store)?;
     Ok(web::Json(result))
-
 }
 
-
-fn filtering(filter: JournalFilter, data: &JournalQuery) -> Vec<JournalResponse> {
+fn filtering(
+    filter: JournalFilter,
+    data: &JournalQuery,
+    bounds: Bounds,
+    store: &dyn Storage,
+) -> Result<Vec<JournalResponse>> {
+    let (before, after) = bounds;
     match filter {
         JournalFilter::All => {
-            let mut notes = filtering(JournalFilter::Notes, data);
-            let mut tasks = filtering(JournalFilter::Tasks, data);
-            let mut threads = filtering(JournalFilter::Threads, data);
+            let mut notes = filtering(JournalFilter::Notes, data, bounds, store)?;
+            let mut tasks = filtering(JournalFilter::Tasks, data, bounds, store)?;
+            let mut threads = filtering(JournalFilter::Threads, data, bounds, store)?;
             notes.append(&mut tasks);
             notes.append(&mut threads);
-            notes
+            Ok(notes)
         }
         JournalFilter::Notes => {
-            let notes = get_all_notes().unwrap();
-            if data.during.is_some() {
-                let tstamp = data.during.unwrap().to_timestamp();
-                let filtered = filter_notes(notes, None, Some(tstamp));
-                filtered.into_iter().map(|n| JournalResponse::Note(n)).collect()
-        } else if data.untouched.is_some() {
-                let tstamp = data.untouched.unwrap().to_timestamp();
-                let filtered = filter_notes(notes, Some(tstamp), None);
-                let result = filtered.into_iter().map(|n| JournalResponse::Note(n)).collect();
-                result
-        } else {
-                let filtered = filter_notes(notes, data.before_to_timestamp(), data.after_to_timestamp());
-                let result = filtered.into_iter().map(|n| JournalResponse::Note(n)).collect();
-                result
+            let mut notes = store.list_notes_between(before, after)?;
+            if let Some(tag) = &data.tag {
+                notes = filter_by_tag(notes, tag);
             }
+            Ok(notes.into_iter().map(JournalResponse::Note).collect())
         }
         JournalFilter::Tasks => {
-            let tasks = get_all_tasks().unwrap();
-            if data.during.is_some() {
-                let tstamp = data.during.unwrap().to_timestamp();
-                let filtered = filter_tasks(tasks, None, Some(tstamp));
-                let result = filtered.into_iter().map(|t| JournalResponse::Task(t)).collect();
-                result
-        } else if data.untouched.is_some() {
-                let tstamp = data.untouched.unwrap().to_timestamp();
-                let filtered = filter_tasks(tasks, Some(tstamp), None);
-                let result = filtered.into_iter().map(|t| JournalResponse::Task(t)).collect();
-                result
-        } else {
-                let filtered = filter_tasks(tasks, data.before_to_timestamp(), data.after_to_timestamp());
-                let result = filtered.into_iter().map(|t| JournalResponse::Task(t)).collect();
-                result
+            let mut tasks = store.list_tasks_between(before, after)?;
+            if let Some(tag) = &data.tag {
+                tasks = filter_by_tag(tasks, tag);
             }
+            Ok(tasks.into_iter().map(JournalResponse::Task).collect())
         }
         JournalFilter::Threads => {
-            let threads = get_all_threads().unwrap();
-            if data.during.is_some() {
-                let tstamp = data.during.unwrap().to_timestamp();
-                let filtered = filter_threads(threads, None, Some(tstamp));
-                let result = filtered.into_iter().map(|t| JournalResponse::Thread(t)).collect();
-                result
-        } else if data.untouched.is_some() {
-                let tstamp = data.untouched.unwrap().to_timestamp();
-                let filtered = filter_threads(threads, Some(tstamp), None);
-                let result = filtered.into_iter().map(|t| JournalResponse::Thread(t)).collect();
-                result
-        } else {
-                let filtered = filter_threads(threads, data.before_to_timestamp(), data.after_to_timestamp());
-                let result = filtered.into_iter().map(|t| JournalResponse::Thread(t)).collect();
-                result
+            // Threads have no tags of their own
+            if data.tag.is_some() {
+                return Ok(Vec::new());
             }
+            let archived = data.archived.unwrap_or_default();
+            let threads = store.list_threads_between(before, after)?;
+            Ok(threads
+                .into_iter()
+                .filter(|t| archived || !t.is_archived())
+                .map(JournalResponse::Thread)
+                .collect())
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::io::{MemoryStorage, Storage};
+    use actix_web::{test, web, App};
+    use eingang::models::{During, ErrorResponse, JournalQuery, JournalResponse, Note};
+    use std::sync::Arc;
+
+    #[actix_rt::test]
+    async fn invalid_dates_are_rejected() {
+        let store: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        store.save_note(&Note::new("note".to_string())).unwrap();
+        let config = Config {
+            time_zone: chrono_tz::Europe::Berlin,
+            ..Default::default()
+        };
+        let mut app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store.clone()))
+                .app_data(web::Data::new(config))
+                .configure(super::config),
+        )
+        .await;
+
+        let query = |after: &str| JournalQuery {
+            after: Some(after.to_string()),
+            ..Default::default()
+        };
+        let req = test::TestRequest::get()
+            .uri("/journal")
+            .set_json(&query("2000-01-01 12:00"))
+            .to_request();
+        let found: Vec<JournalResponse> = test::read_response_json(&mut app, req).await;
+        assert_eq!(found.len(), 1);
+
+        let req = test::TestRequest::get()
+            .uri("/journal")
+            .set_json(&query("2020-13-01"))
+            .to_request();
+        let error: ErrorResponse = test::read_response_json(&mut app, req).await;
+        assert_eq!(error.status, 400);
+
+        let query = JournalQuery {
+            during: Some(During::Range("today".to_string())),
+            ..Default::default()
+        };
+        let req = test::TestRequest::get()
+            .uri("/journal")
+            .set_json(&query)
+            .to_request();
+        let found: Vec<JournalResponse> = test::read_response_json(&mut app, req).await;
+        assert_eq!(found.len(), 1);
+    }
+}
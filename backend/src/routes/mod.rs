@@ -2,11 +2,14 @@
 //!
 //! **Ideally all routes are just one/two liner and simple function calls to the backend**
 //!
+use crate::config::Config;
+use crate::io::{self, EingangError};
 use crate::STORAGE;
-use actix_web::{web, HttpRequest, HttpResponse, Responder, Result};
+use actix_web::{web, HttpRequest, HttpResponse, Result};
 use eingang::models::Data;
 use serde_qs as qs;
-use std::{fs::File, io::Write};
+use std::fs::File;
+use uuid::Uuid;
 
 // TODO Make oneliner to io
 pub fn config(cfg: &mut web::ServiceConfig) {
@@ -35,7 +38,7 @@ pub async fn index(req: HttpRequest) -> Result<web::Json<Data>> {
 // Use native query struct `actix_web::web::Query` to (un)serialize information about the object
 // e.g. http://localhost:8081/query?value=2423&id=746217fd-da9c-4139-8b8e-cf4089dd680e
 async fn saving(q: web::Query<Data>) -> Result<web::Json<Data>> {
-    let d = q.clone();
+    let d = *q;
     Ok(web::Json(d))
 }
 
@@ -49,36 +52,38 @@ async fn serialize(req: HttpRequest) -> Result<web::Json<Data>> {
 
 // Send data to server and safe it on disk
 // e.g. curl -v -d '{"value":2423,"id":"746217fd-da9c-4139-8b8e-cf4089dd680e"}' -H 'Content-Type: application/json' http://localhost:8081/save
-async fn permanent(data: web::Json<Data>) -> impl Responder {
-    let buffer = File::create(STORAGE).unwrap();
-    let mut writer = std::io::BufWriter::new(buffer);
-    let _ = serde_json::to_writer_pretty(&mut writer, &data.into_inner()).unwrap();
-    writer.flush().unwrap();
-    HttpResponse::NoContent()
+async fn permanent(data: web::Json<Data>, config: web::Data<Config>) -> EingangHttpResponse {
+    let content = serde_json::to_vec_pretty(&data.into_inner())?;
+    io::folder::write_atomic(&config.data_dir.join(STORAGE), &content)?;
+    Ok(HttpResponse::NoContent().finish())
 }
 
-async fn loading(_: HttpRequest) -> Result<web::Json<Data>> {
-    let buffer = File::open(STORAGE).unwrap();
+async fn loading(_: HttpRequest, config: web::Data<Config>) -> EingangResponse<Data> {
+    let buffer = File::open(config.data_dir.join(STORAGE))?;
     let rdr = std::io::BufReader::new(buffer);
-    let data: Data = serde_json::from_reader(rdr).unwrap();
+    let data: Data = serde_json::from_reader(rdr)?;
     Ok(web::Json(data))
 }
 
+pub mod admin;
+pub mod find;
+pub mod journal;
 pub mod notes;
+pub mod search;
+pub mod tags;
 pub mod tasks;
 pub mod threads;
-pub mod journal;
 
 /// Return a vector of json serializeable data
-pub type EingangVecResponse<T> = Result<web::Json<Vec<T>>, web::HttpResponse>;
+pub type EingangVecResponse<T> = Result<web::Json<Vec<T>>, EingangError>;
 
 /// Return a json representation of serializable data
-pub type EingangResponse<T> = Result<web::Json<T>, web::HttpResponse>;
+pub type EingangResponse<T> = Result<web::Json<T>, EingangError>;
 
-fn parse_uuid(req: HttpRequest) -> String {
-    req.match_info()
-        .get("uuid")
-        .unwrap() // TODO Better parsing, since this could panic
-        .parse()
-        .unwrap()
+/// Return a plain http response, e.g. for actions without content
+pub type EingangHttpResponse = Result<HttpResponse, EingangError>;
+
+fn parse_uuid(req: HttpRequest) -> Result<Uuid, EingangError> {
+    let uuid = req.match_info().get("uuid").unwrap_or_default();
+    io::parse_uuid(uuid)
 }
@@ -7,11 +7,22 @@
 //! The current list of elements are:
 //! - Http responses send by the backend to the user
 //! - Available routes for the user
-//! - Helper functions for interaction with the underlying filesystem
-use super::{parse_uuid, EingangResponse, EingangVecResponse};
-use crate::io::{get_all_notes as gan, read_note, save_note, Location};
+//! - Helper functions for interaction with the underlying storage
+//!
+//! ## Revisions
+//! Every save keeps the previous version of a note. Revisions are numbered
+//! from 1 (oldest), the current version has the highest number.
+//! - `/notes/{uuid}/revisions`: List all revisions
+//! - `/notes/{uuid}/revisions/{n}`: Return revision `n`
+//! - `/notes/{uuid}/revisions/{n}/restore`: Save revision `n` as new version
+//! - `/notes/{uuid}/diff?from=1&to=3`: Line-based diff of the content, `to`
+//!   defaults to the current version and `from` to the one before `to`
+use super::{parse_uuid, EingangHttpResponse, EingangResponse, EingangVecResponse};
+use crate::io::{fsck, EingangError, LinkIndex, Storage};
 use actix_web::{web, HttpRequest, HttpResponse};
-use eingang::models::{Idable, Note, NoteQuery};
+use eingang::models::{filter_by_tag, normalize_tags, Idable, LinkedElement, LinksResponse};
+use eingang::models::{ChangeKind, DiffLine, DiffQuery, RevisionDiff, RevisionInfo};
+use eingang::models::{Note, NoteQuery};
 
 /// Configure routes for Notes
 pub fn config(cfg: &mut web::ServiceConfig) {
@@ -20,50 +31,72 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("/notes/{uuid}").route(web::get().to(get_note)));
     cfg.service(web::resource("/notes/{uuid}/delete").route(web::delete().to(delete_note)));
     cfg.service(web::resource("/notes/{uuid}/update").route(web::patch().to(update_note)));
+    cfg.service(web::resource("/notes/{uuid}/links").route(web::get().to(get_links)));
+    cfg.service(web::resource("/notes/{uuid}/backlinks").route(web::get().to(get_backlinks)));
+    cfg.service(web::resource("/notes/{uuid}/revisions").route(web::get().to(get_revisions)));
+    cfg.service(web::resource("/notes/{uuid}/revisions/{n}").route(web::get().to(get_revision)));
+    cfg.service(
+        web::resource("/notes/{uuid}/revisions/{n}/restore")
+            .route(web::post().to(restore_revision)),
+    );
+    cfg.service(web::resource("/notes/{uuid}/diff").route(web::get().to(get_diff)));
 }
 
 /// Return all Notes
 ///
-/// This route returns all notes saved on the filesystem.
-async fn get_all_notes(_: HttpRequest) -> EingangVecResponse<Note> {
-    match gan() {
-        Ok(result) => Ok(web::Json(result)),
-        Err(e) => Err(HttpResponse::BadRequest().json(format!("{}", e)))
+/// This route returns all notes saved in the storage. With `tag=work` only
+/// notes tagged with `work` or e.g. `work/clienta` are returned.
+async fn get_all_notes(
+    _: HttpRequest,
+    q: web::Query<NoteQuery>,
+    store: web::Data<dyn Storage>,
+) -> EingangVecResponse<Note> {
+    let notes = store.list_notes()?;
+    match &q.tag {
+        Some(tag) => Ok(web::Json(filter_by_tag(notes, tag))),
+        None => Ok(web::Json(notes)),
     }
 }
 
-async fn create_new_note(q: web::Json<NoteQuery>) -> HttpResponse {
+async fn create_new_note(
+    q: web::Json<NoteQuery>,
+    store: web::Data<dyn Storage>,
+) -> EingangHttpResponse {
     let nq = q.into_inner();
-    if nq.content.is_none() {
-        return HttpResponse::BadRequest().json("Field 'content' is missing");
+    let content = match nq.content {
+        Some(c) => c,
+        None => {
+            return Err(EingangError::BadRequest(
+                "Field 'content' is missing".into(),
+            ))
+        }
     };
-    let content = nq.content.unwrap();
     let title = nq.title.unwrap_or_default();
-    let note = Note::with_title(content, title);
-    save_note(&note);
-    HttpResponse::Ok().json(note.get_uuid().to_string()) // TODO Better response messages. Maybe { http_code: 321, message: "" }
+    let mut note = Note::with_title(content, title);
+    note.tags = normalize_tags(nq.tags.unwrap_or_default());
+    store.save_note(&note)?;
+    Ok(HttpResponse::Ok().json(note.get_uuid().to_string()))
 }
 
-async fn get_note(req: HttpRequest) -> EingangResponse<Note> {
-    let uuid: String = parse_uuid(req);
-    match read_note(&uuid) {
-        Ok(note) => Ok(web::Json(note)),
-        Err(e) => Err(HttpResponse::BadRequest().json(format!("{}", e)))
-    }
+async fn get_note(req: HttpRequest, store: web::Data<dyn Storage>) -> EingangResponse<Note> {
+    let uuid = parse_uuid(req)?;
+    Ok(web::Json(store.get_note(&uuid)?))
 }
 
-async fn delete_note(req: HttpRequest) -> HttpResponse {
-    let uuid: String = parse_uuid(req);
-    let file = Location::Note.create_filename(&uuid);
-    match std::fs::remove_file(file) {
-        Ok(_) => HttpResponse::NoContent().json("Successful"),
-        _ => HttpResponse::BadRequest().json("UUID is not associated"),
-    }
+async fn delete_note(req: HttpRequest, store: web::Data<dyn Storage>) -> EingangHttpResponse {
+    let uuid = parse_uuid(req)?;
+    store.delete_note(&uuid)?;
+    fsck::detach(&**store, &uuid)?;
+    Ok(HttpResponse::NoContent().json("Successful"))
 }
 
-async fn update_note(req: HttpRequest, q: web::Json<NoteQuery>) -> HttpResponse {
-    let uuid: String = parse_uuid(req);
-    let mut note = read_note(&uuid).unwrap();
+async fn update_note(
+    req: HttpRequest,
+    q: web::Json<NoteQuery>,
+    store: web::Data<dyn Storage>,
+) -> EingangHttpResponse {
+    let uuid = parse_uuid(req)?;
+    let mut note = store.get_note(&uuid)?;
     let nq = q.into_inner();
 
     let mut note_changed = false;
@@ -75,9 +108,267 @@ async fn update_note(req: HttpRequest, q: web::Json<NoteQuery>) -> HttpResponse
         note.title = t;
         note_changed = true
     }
+    if let Some(t) = nq.tags {
+        note.tags = normalize_tags(t);
+        note_changed = true
+    }
     if note_changed {
         note.update_modified_date();
-        save_note(&note)
+        store.save_note(&note)?;
+    }
+    Ok(HttpResponse::NoContent().json("Successful"))
+}
+
+/// Return all elements the note links to and all links which do not match
+/// any element
+async fn get_links(
+    req: HttpRequest,
+    store: web::Data<dyn Storage>,
+    links: web::Data<LinkIndex>,
+) -> EingangResponse<LinksResponse> {
+    let uuid = parse_uuid(req)?;
+    store.get_note(&uuid)?;
+    Ok(web::Json(links.links(&uuid)))
+}
+
+/// Return all notes and tasks linking to the note
+async fn get_backlinks(
+    req: HttpRequest,
+    store: web::Data<dyn Storage>,
+    links: web::Data<LinkIndex>,
+) -> EingangVecResponse<LinkedElement> {
+    let uuid = parse_uuid(req)?;
+    store.get_note(&uuid)?;
+    Ok(web::Json(links.backlinks(&uuid)))
+}
+
+/// All versions of a note, oldest first, ending with the current one
+fn history(store: &dyn Storage, uuid: &uuid::Uuid) -> Result<Vec<Note>, EingangError> {
+    let current = store.get_note(uuid)?;
+    let mut revisions = store.note_revisions(uuid)?;
+    revisions.push(current);
+    Ok(revisions)
+}
+
+/// Return revision `n` (starting at 1) out of the `history` of a note
+fn revision(history: &[Note], n: usize) -> Result<&Note, EingangError> {
+    n.checked_sub(1)
+        .and_then(|i| history.get(i))
+        .ok_or_else(|| {
+            let uuid = history.last().map(|c| c.get_uuid()).unwrap_or_default();
+            EingangError::NotFound(format!("Revision {} of note {}", n, uuid))
+        })
+}
+
+fn parse_revision(req: &HttpRequest) -> Result<usize, EingangError> {
+    let n = req.match_info().get("n").unwrap_or_default();
+    n.parse()
+        .map_err(|_| EingangError::BadRequest(format!("Invalid revision '{}'", n)))
+}
+
+async fn get_revisions(
+    req: HttpRequest,
+    store: web::Data<dyn Storage>,
+) -> EingangVecResponse<RevisionInfo> {
+    let uuid = parse_uuid(req)?;
+    let revisions = history(&**store, &uuid)?
+        .iter()
+        .enumerate()
+        .map(|(i, note)| RevisionInfo {
+            revision: i + 1,
+            title: note.title.clone(),
+            last_modified: note.last_modified(),
+        })
+        .collect();
+    Ok(web::Json(revisions))
+}
+
+async fn get_revision(req: HttpRequest, store: web::Data<dyn Storage>) -> EingangResponse<Note> {
+    let n = parse_revision(&req)?;
+    let uuid = parse_uuid(req)?;
+    let history = history(&**store, &uuid)?;
+    Ok(web::Json(revision(&history, n)?.clone()))
+}
+
+/// Copy title, content and tags of a revision into the current note. The
+/// replaced version is kept as revision, so restoring can be undone.
+async fn restore_revision(req: HttpRequest, store: web::Data<dyn Storage>) -> EingangHttpResponse {
+    let n = parse_revision(&req)?;
+    let uuid = parse_uuid(req)?;
+    let history = history(&**store, &uuid)?;
+    let old = revision(&history, n)?;
+    let mut note = history[history.len() - 1].clone();
+    note.title = old.title.clone();
+    note.content = old.content.clone();
+    note.tags = old.tags.clone();
+    note.update_modified_date();
+    store.save_note(&note)?;
+    Ok(HttpResponse::NoContent().json("Successful"))
+}
+
+async fn get_diff(
+    req: HttpRequest,
+    q: web::Query<DiffQuery>,
+    store: web::Data<dyn Storage>,
+) -> EingangResponse<RevisionDiff> {
+    let uuid = parse_uuid(req)?;
+    let history = history(&**store, &uuid)?;
+    let to = q.to.unwrap_or(history.len());
+    let from = q.from.unwrap_or(to.saturating_sub(1).max(1));
+    let old = &revision(&history, from)?.content;
+    let new = &revision(&history, to)?.content;
+    let lines = similar::TextDiff::from_lines(old, new)
+        .iter_all_changes()
+        .map(|change| DiffLine {
+            change: match change.tag() {
+                similar::ChangeTag::Equal => ChangeKind::Equal,
+                similar::ChangeTag::Insert => ChangeKind::Insert,
+                similar::ChangeTag::Delete => ChangeKind::Delete,
+            },
+            line: change
+                .value()
+                .trim_end_matches(&['\r', '\n'][..])
+                .to_string(),
+        })
+        .collect();
+    Ok(web::Json(RevisionDiff { from, to, lines }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::io::{IndexedStorage, MemoryStorage, Storage};
+    use actix_web::{test, web, App};
+    use eingang::models::{ErrorKind, ErrorResponse, Idable, LinkedElement, Note, NoteQuery};
+    use eingang::models::{RevisionDiff, RevisionInfo};
+    use std::sync::Arc;
+
+    #[actix_rt::test]
+    async fn create_and_read_note_from_memory() {
+        let store: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let mut app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store.clone()))
+                .configure(super::config),
+        )
+        .await;
+
+        let query = NoteQuery {
+            title: Some("title".to_string()),
+            content: Some("content".to_string()),
+            ..Default::default()
+        };
+        let req = test::TestRequest::post()
+            .uri("/notes/new")
+            .set_json(&query)
+            .to_request();
+        let uuid: String = test::read_response_json(&mut app, req).await;
+        assert_eq!(store.list_notes().unwrap().len(), 1);
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/notes/{}", uuid))
+            .to_request();
+        let note: Note = test::read_response_json(&mut app, req).await;
+        assert_eq!(note.title, "title");
+        assert_eq!(note.content, "content");
+    }
+
+    #[actix_rt::test]
+    async fn missing_and_invalid_uuids_are_errors() {
+        let store: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let mut app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store))
+                .configure(super::config),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/notes/{}", uuid::Uuid::new_v4()))
+            .to_request();
+        let error: ErrorResponse = test::read_response_json(&mut app, req).await;
+        assert_eq!(error.status, 404);
+        assert_eq!(error.kind, ErrorKind::NotFound);
+
+        let req = test::TestRequest::get().uri("/notes/nonsense").to_request();
+        let error: ErrorResponse = test::read_response_json(&mut app, req).await;
+        assert_eq!(error.status, 400);
+        assert_eq!(error.kind, ErrorKind::InvalidUuid);
+    }
+
+    #[actix_rt::test]
+    async fn backlinks_are_updated_on_save() {
+        let store = IndexedStorage::build(Arc::new(MemoryStorage::new())).unwrap();
+        let links = web::Data::from(store.links());
+        let store: Arc<dyn Storage> = Arc::new(store);
+        let target = Note::with_title("content".to_string(), "Target".to_string());
+        store.save_note(&target).unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store.clone()))
+                .app_data(links)
+                .configure(super::config),
+        )
+        .await;
+
+        let query = NoteQuery {
+            title: Some("source".to_string()),
+            content: Some("Link to [[target]]".to_string()),
+            ..Default::default()
+        };
+        let req = test::TestRequest::post()
+            .uri("/notes/new")
+            .set_json(&query)
+            .to_request();
+        let uuid: String = test::read_response_json(&mut app, req).await;
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/notes/{}/backlinks", target.get_uuid()))
+            .to_request();
+        let backlinks: Vec<LinkedElement> = test::read_response_json(&mut app, req).await;
+        assert_eq!(backlinks.len(), 1);
+        assert_eq!(backlinks[0].uuid.to_string(), uuid);
+    }
+
+    #[actix_rt::test]
+    async fn restore_and_diff_revisions() {
+        let store: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let mut note = Note::with_title("first\nline".to_string(), "title".to_string());
+        store.save_note(&note).unwrap();
+        note.content = "second\nline".to_string();
+        store.save_note(&note).unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store.clone()))
+                .configure(super::config),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/notes/{}/diff", note.get_uuid()))
+            .to_request();
+        let diff: RevisionDiff = test::read_response_json(&mut app, req).await;
+        assert_eq!((diff.from, diff.to), (1, 2));
+        assert_eq!(diff.to_string(), "-first\n+second\n line\n");
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/notes/{}/revisions/1/restore", note.get_uuid()))
+            .to_request();
+        test::call_service(&mut app, req).await;
+        assert_eq!(
+            store.get_note(&note.get_uuid()).unwrap().content,
+            "first\nline"
+        );
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/notes/{}/revisions", note.get_uuid()))
+            .to_request();
+        let revisions: Vec<RevisionInfo> = test::read_response_json(&mut app, req).await;
+        assert_eq!(revisions.len(), 3);
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/notes/{}/revisions/4", note.get_uuid()))
+            .to_request();
+        let error: ErrorResponse = test::read_response_json(&mut app, req).await;
+        assert_eq!(error.kind, ErrorKind::NotFound);
     }
-    HttpResponse::NoContent().json("Successful")
 }
@@ -0,0 +1,111 @@
+//! # Routes: Search
+//!
+//! ## Routes
+//! - `/search?q=...`: Return notes and tasks matching all words of `q`, best
+//!   hits first. Words are matched by their stem, text in double quotes as
+//!   phrase, e.g. `q="weekly review" projects`.
+//!   - `limit=n`: Return at most `n` hits (default: 20)
+//!
+//! Hits are checked against the storage, so elements which cannot be read
+//! anymore, e.g. because they were quarantined, are left out.
+use super::EingangVecResponse;
+use crate::io::{EingangError, SearchIndex, Storage};
+use actix_web::{web, HttpRequest};
+use eingang::models::eingang::search::SEARCH_LIMIT;
+use eingang::models::{SearchHit, SearchQuery};
+
+/// Configure routes for Search
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/search").route(web::get().to(search)));
+}
+
+async fn search(
+    _: HttpRequest,
+    q: web::Query<SearchQuery>,
+    index: web::Data<SearchIndex>,
+    store: web::Data<dyn Storage>,
+) -> EingangVecResponse<SearchHit> {
+    let query = match q.q.as_deref() {
+        Some(query) if !query.trim().is_empty() => query,
+        _ => {
+            return Err(EingangError::BadRequest(
+                "Query parameter 'q' is missing".into(),
+            ))
+        }
+    };
+    let limit = q.limit.unwrap_or(SEARCH_LIMIT);
+    let hits = index
+        .search(query, limit)
+        .into_iter()
+        .filter(|hit| match hit.kind.as_str() {
+            "Note" => store.get_note(&hit.uuid).is_ok(),
+            _ => store.get_task(&hit.uuid).is_ok(),
+        })
+        .collect();
+    Ok(web::Json(hits))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::io::{IndexedStorage, MemoryStorage, Storage};
+    use actix_web::{test, web, App};
+    use eingang::models::{ErrorResponse, Idable, Note, SearchHit, Task};
+    use std::sync::Arc;
+
+    #[actix_rt::test]
+    async fn search_follows_saves_and_deletes() {
+        let store = IndexedStorage::build(Arc::new(MemoryStorage::new())).unwrap();
+        let index = web::Data::from(store.search());
+        let store: Arc<dyn Storage> = Arc::new(store);
+        let note = Note::with_title("Planning the garden".to_string(), "Garden".to_string());
+        let task = Task::with_title("Plan the holidays".to_string(), "Holidays".to_string());
+        store.save_note(&note).unwrap();
+        store.save_task(&task).unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .app_data(index)
+                .app_data(web::Data::from(store.clone()))
+                .configure(super::config),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/search?q=plan").to_request();
+        let hits: Vec<SearchHit> = test::read_response_json(&mut app, req).await;
+        assert_eq!(hits.len(), 2);
+
+        store.delete_task(&task.get_uuid()).unwrap();
+        let req = test::TestRequest::get().uri("/search?q=plan").to_request();
+        let hits: Vec<SearchHit> = test::read_response_json(&mut app, req).await;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].uuid, note.get_uuid());
+        assert_eq!(hits[0].snippet, "<mark>Planning</mark> the garden");
+
+        let req = test::TestRequest::get().uri("/search").to_request();
+        let error: ErrorResponse = test::read_response_json(&mut app, req).await;
+        assert_eq!(error.status, 400);
+    }
+
+    #[actix_rt::test]
+    async fn elements_gone_from_the_storage_are_no_hits() {
+        let inner = Arc::new(MemoryStorage::new());
+        let store = IndexedStorage::build(inner.clone()).unwrap();
+        let index = store.search();
+        let store: Arc<dyn Storage> = Arc::new(store);
+        let note = Note::with_title("Planning the garden".to_string(), "Garden".to_string());
+        store.save_note(&note).unwrap();
+        // Lost without the index noticing, like a quarantined file
+        inner.delete_note(&note.get_uuid()).unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(index.clone()))
+                .app_data(web::Data::from(store.clone()))
+                .configure(super::config),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/search?q=plan").to_request();
+        let hits: Vec<SearchHit> = test::read_response_json(&mut app, req).await;
+        assert!(hits.is_empty());
+        assert!(index.search("plan", 20).is_empty());
+    }
+}
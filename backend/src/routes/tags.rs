@@ -0,0 +1,71 @@
+//! # Routes: Tags
+//!
+//! ## Routes
+//! - `/tags`: Return all tags of notes and tasks with the number of elements
+//!   using them. Parent tags count the elements of all their descendants,
+//!   e.g. `work` counts `work/clienta` as well.
+use super::EingangVecResponse;
+use crate::io::Storage;
+use actix_web::{web, HttpRequest};
+use eingang::models::eingang::tag::tag_ancestors;
+use eingang::models::{TagCount, Tagged, Tags};
+use std::collections::BTreeMap;
+
+/// Configure routes for Tags
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/tags").route(web::get().to(get_all_tags)));
+}
+
+async fn get_all_tags(
+    _: HttpRequest,
+    store: web::Data<dyn Storage>,
+) -> EingangVecResponse<TagCount> {
+    let notes = store.list_notes()?;
+    let tasks = store.list_tasks()?;
+    let tags = notes
+        .iter()
+        .map(Tagged::tags)
+        .chain(tasks.iter().map(Tagged::tags));
+    Ok(web::Json(count_tags(tags)))
+}
+
+fn count_tags<I: Iterator<Item = Tags>>(tags: I) -> Vec<TagCount> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for element_tags in tags {
+        let all: Tags = element_tags.iter().flat_map(|t| tag_ancestors(t)).collect();
+        for tag in all {
+            *counts.entry(tag).or_default() += 1;
+        }
+    }
+    counts
+        .into_iter()
+        .map(|(tag, count)| TagCount { tag, count })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::count_tags;
+    use eingang::models::normalize_tags;
+
+    #[test]
+    fn parents_count_descendants_once() {
+        let tags = vec![
+            normalize_tags(vec!["work/a", "work/b"]),
+            normalize_tags(vec!["work", "home"]),
+        ];
+        let counts: Vec<(String, usize)> = count_tags(tags.into_iter())
+            .into_iter()
+            .map(|c| (c.tag, c.count))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![
+                ("home".to_string(), 1),
+                ("work".to_string(), 2),
+                ("work/a".to_string(), 1),
+                ("work/b".to_string(), 1),
+            ]
+        );
+    }
+}
@@ -10,80 +10,246 @@
 //!   - `status=open`: Return all open tasks
 //!   - `status=waiting`: Return all waiting tasks
 //!   - if `status` is nonsense, it will be ignored
+//!   - `tag=work`: Return all tasks tagged with `work` or e.g. `work/clienta`
+//!   - `sort=[created|due|urgency]`: Order of the tasks (default: `created`),
+//!     `urgency` adds the score to every task
+//! - `/tasks/next`: Return all open tasks, which are not blocked by other tasks
 //! - `/tasks/{uuid}`: Return a specific task
-//! - `/tasks/{uuid}/update`: Edit a specific task
+//!   - `dependencies=true`: Return the task with the chain of blocking tasks
+//! - `/tasks/{uuid}/update`: Edit a specific task (`tags` replaces all tags)
+//!   - `status` changes are checked against `TaskStatus::can_change_to`
+//!     (409 otherwise) and recorded in the task's `history`
 //! - `/tasks/{uuid}/delete`: Delete a specific task
 //! - `/tasks/new`: Create new task
-use super::{parse_uuid, EingangResponse, EingangVecResponse};
-use crate::io::{get_all_tasks as gat, read_task, save_task, Location};
+//!   - a `status` other than `open` is recorded in the task's `history`
+//!   - `due` and `scheduled` accept `YYYY-MM-DD`, `YYYY-MM-DD HH:MM[:SS]` or
+//!     RFC 3339 timestamps, those without offset in the configured `time_zone`
+//!   - `recurrence` accepts rules like `FREQ=WEEKLY;BYDAY=MO`, closing such a
+//!     task creates its next instance (only once, even if it is reopened)
+//!   - `blocked_by` lists the UUIDs of blocking tasks, cycles are rejected
+//!   - `priority` is one of `high`, `medium`, `low` or `none`
+//! - `/tasks/agenda`: Return overdue, due today, upcoming and scheduled tasks
+//!   - `days=7`: Number of days after today counted as upcoming
+use super::{parse_uuid, EingangHttpResponse, EingangResponse, EingangVecResponse};
+use crate::config::Config;
+use crate::io::{self, fsck, EingangError, Storage};
 use actix_web::{web, HttpRequest, HttpResponse};
-use eingang::models::{Idable, Task, TaskQuery, TaskStatus};
+use chrono_tz::Tz;
+use eingang::models::eingang::agenda::UPCOMING_DAYS;
+use eingang::models::eingang::dependency::{find_cycle, next_actions};
+use eingang::models::eingang::{parse_moment, Timestamp};
+use eingang::models::{normalize_tags, Agenda, AgendaQuery, Idable, Priority, ScoredTask};
+use eingang::models::{DependencyNode, DependencyQuery, Recurrence, Task, TaskQuery};
+use eingang::models::{Tagged, TaskSort, TaskStatus, TaskUuid};
+use std::collections::HashMap;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("/tasks").route(web::get().to(get_all_tasks)));
     cfg.service(web::resource("/tasks/new").route(web::post().to(create_new_task)));
+    cfg.service(web::resource("/tasks/agenda").route(web::get().to(get_agenda)));
+    cfg.service(web::resource("/tasks/next").route(web::get().to(get_next_actions)));
     cfg.service(web::resource("/tasks/{uuid}").route(web::get().to(get_task)));
     cfg.service(web::resource("/tasks/{uuid}/delete").route(web::delete().to(delete_task)));
     cfg.service(web::resource("/tasks/{uuid}/update").route(web::patch().to(update_task)));
 }
 
-async fn get_all_tasks(_: HttpRequest, q: web::Query<TaskQuery>) -> EingangVecResponse<Task> {
-    let (do_filter, filter) = match q.into_inner().status {
-        Some(s) => match TaskStatus::from(s) {
-            Some(c) => (true, c),
-            _ => (false, TaskStatus::default()),
-        },
-        _ => (false, TaskStatus::default()),
+async fn get_all_tasks(
+    _: HttpRequest,
+    q: web::Query<TaskQuery>,
+    store: web::Data<dyn Storage>,
+    config: web::Data<Config>,
+) -> EingangHttpResponse {
+    let tq = q.into_inner();
+    let status = tq.status.and_then(TaskStatus::from);
+    let tag = tq.tag;
+    let keep = |t: &Task| {
+        status.is_none_or(|s| t.status == s) && tag.as_ref().is_none_or(|tag| t.has_tag(tag))
     };
-    let temp = gat().unwrap_or_default().into_iter();
-    if do_filter {
-        Ok(web::Json(temp.filter(|k| k.status == filter).collect()))
-    } else {
-        Ok(web::Json(temp.collect()))
+    if tq.sort == Some(TaskSort::Urgency) {
+        // Blocking relations need all tasks, so filter after scoring
+        let scored: Vec<ScoredTask> = config
+            .urgency
+            .sort(store.list_tasks()?, chrono::Utc::now())
+            .into_iter()
+            .filter(|s| keep(&s.task))
+            .collect();
+        return Ok(HttpResponse::Ok().json(scored));
     }
+
+    let mut tasks = match status {
+        Some(status) => store.list_tasks_with_status(status)?,
+        None => store.list_tasks()?,
+    };
+    tasks.retain(keep);
+    match tq.sort {
+        Some(TaskSort::Due) => {
+            tasks.sort_by_key(|t| (t.due.is_none(), t.due, t.created_on(), t.get_uuid()))
+        }
+        _ => tasks.sort_by_key(|t| (t.created_on(), t.get_uuid())),
+    }
+    Ok(HttpResponse::Ok().json(tasks))
+}
+
+async fn get_agenda(
+    _: HttpRequest,
+    q: web::Query<AgendaQuery>,
+    store: web::Data<dyn Storage>,
+    config: web::Data<Config>,
+) -> EingangResponse<Agenda> {
+    let days = q.days.unwrap_or(UPCOMING_DAYS);
+    let now = chrono::Utc::now().with_timezone(&config.time_zone);
+    let agenda = Agenda::new(store.list_tasks()?, now, days);
+    Ok(web::Json(agenda))
+}
+
+/// Parse a date of a task, where an empty value removes the date
+fn parse_date(
+    field: &str,
+    value: &str,
+    end_of_day: bool,
+    tz: &Tz,
+) -> Result<Option<Timestamp>, EingangError> {
+    if value.trim().is_empty() {
+        return Ok(None);
+    }
+    match parse_moment(value, end_of_day, tz) {
+        Some(t) => Ok(Some(t)),
+        None => Err(EingangError::BadRequest(format!(
+            "Invalid date '{}' for field '{}'",
+            value, field
+        ))),
+    }
+}
+
+/// Parse a recurrence rule of a task, where an empty value removes the rule
+fn parse_recurrence(value: &str) -> Result<Option<Recurrence>, EingangError> {
+    if value.trim().is_empty() {
+        return Ok(None);
+    }
+    value.parse().map(Some).map_err(EingangError::BadRequest)
+}
+
+fn parse_priority(priority: &str) -> Result<Priority, EingangError> {
+    Priority::from(priority)
+        .ok_or_else(|| EingangError::BadRequest(format!("Unknown priority '{}'", priority)))
 }
 
-async fn create_new_task(q: web::Json<TaskQuery>) -> HttpResponse {
+fn parse_status(status: String) -> Result<TaskStatus, EingangError> {
+    TaskStatus::from(status.clone())
+        .ok_or_else(|| EingangError::BadRequest(format!("Unknown status '{}'", status)))
+}
+
+async fn create_new_task(
+    q: web::Json<TaskQuery>,
+    store: web::Data<dyn Storage>,
+    config: web::Data<Config>,
+) -> EingangHttpResponse {
     let tq = q.into_inner();
-    if tq.content.is_none() {
-        return HttpResponse::BadRequest().json("Field 'content' is missing");
-    };
-    // TODO Write a better matching, maybe with list of accepted values
-    let mut status = TaskStatus::default();
-    if tq.status.is_some() {
-        let stst = tq.status.unwrap();
-        match TaskStatus::from(stst) {
-            Some(c) => status = c,
-            _ => return HttpResponse::BadRequest().json("Unknown status"),
+    let content = match tq.content {
+        Some(c) => c,
+        None => {
+            return Err(EingangError::BadRequest(
+                "Field 'content' is missing".into(),
+            ))
         }
     };
-    let content = tq.content.unwrap();
     let title = tq.title.unwrap_or_default();
-    let task = Task::with_title_and_status(content, title, status);
-    save_task(&task);
-    HttpResponse::Ok().json(task.get_uuid().to_string())
+    let mut task = Task::with_title_and_status(content, title, TaskStatus::default());
+    // Other initial statuses are changes, e.g. of tasks created as closed
+    if let Some(s) = tq.status {
+        let status = parse_status(s)?;
+        task.set_status(status).map_err(EingangError::Conflict)?;
+    }
+    task.tags = normalize_tags(tq.tags.unwrap_or_default());
+    if let Some(due) = tq.due {
+        task.due = parse_date("due", &due, true, &config.time_zone)?;
+    }
+    if let Some(scheduled) = tq.scheduled {
+        task.scheduled = parse_date("scheduled", &scheduled, false, &config.time_zone)?;
+    }
+    if let Some(recurrence) = tq.recurrence {
+        task.recurrence = parse_recurrence(&recurrence)?;
+    }
+    if let Some(blockers) = tq.blocked_by {
+        set_blockers(&mut task, blockers, &**store)?;
+    }
+    if let Some(priority) = tq.priority {
+        task.priority = parse_priority(&priority)?;
+    }
+    store.save_task(&task)?;
+    Ok(HttpResponse::Ok().json(task.get_uuid().to_string()))
 }
 
-async fn get_task(req: HttpRequest) -> EingangResponse<Task> {
-    let uuid: String = parse_uuid(req);
-    match read_task(&uuid) {
-        Ok(note) => Ok(web::Json(note)),
-        Err(e) => Err(HttpResponse::BadRequest().json(format!("{}", e)))
+async fn get_task(
+    req: HttpRequest,
+    q: web::Query<DependencyQuery>,
+    store: web::Data<dyn Storage>,
+) -> EingangHttpResponse {
+    let uuid = parse_uuid(req)?;
+    let task = store.get_task(&uuid)?;
+    if q.dependencies {
+        let chain = DependencyNode::new(task, &task_map(&**store)?);
+        Ok(HttpResponse::Ok().json(chain))
+    } else {
+        Ok(HttpResponse::Ok().json(task))
     }
 }
 
-async fn delete_task(req: HttpRequest) -> HttpResponse {
-    let uuid: String = parse_uuid(req);
-    let file = Location::Task.create_filename(&uuid);
-    match std::fs::remove_file(file) {
-        Ok(_) => HttpResponse::NoContent().json("Successful"),
-        _ => HttpResponse::BadRequest().json("UUID is not associated"),
+async fn get_next_actions(
+    _: HttpRequest,
+    store: web::Data<dyn Storage>,
+) -> EingangVecResponse<Task> {
+    Ok(web::Json(next_actions(&task_map(&**store)?)))
+}
+
+fn task_map(store: &dyn Storage) -> Result<HashMap<TaskUuid, Task>, EingangError> {
+    let tasks = store.list_tasks()?;
+    Ok(tasks.into_iter().map(|t| (t.get_uuid(), t)).collect())
+}
+
+/// Replace the tasks blocking `task`, if they exist and do not create a cycle
+fn set_blockers(
+    task: &mut Task,
+    blockers: Vec<String>,
+    store: &dyn Storage,
+) -> Result<(), EingangError> {
+    let mut blocked_by = Vec::new();
+    for blocker in blockers {
+        let uuid = io::parse_uuid(&blocker)?;
+        if !blocked_by.contains(&uuid) {
+            blocked_by.push(uuid);
+        }
     }
+    let tasks = task_map(store)?;
+    if let Some(missing) = blocked_by.iter().find(|b| !tasks.contains_key(b)) {
+        return Err(EingangError::NotFound(format!("Task {}", missing)));
+    }
+    if let Some(cycle) = find_cycle(task.get_uuid(), &blocked_by, &tasks) {
+        let cycle: Vec<String> = cycle.iter().map(|u| u.to_string()).collect();
+        return Err(EingangError::Conflict(format!(
+            "Dependency cycle: {}",
+            cycle.join(" -> ")
+        )));
+    }
+    task.blocked_by = blocked_by;
+    Ok(())
+}
+
+async fn delete_task(req: HttpRequest, store: web::Data<dyn Storage>) -> EingangHttpResponse {
+    let uuid = parse_uuid(req)?;
+    store.delete_task(&uuid)?;
+    fsck::detach(&**store, &uuid)?;
+    Ok(HttpResponse::NoContent().json("Successful"))
 }
 
-async fn update_task(req: HttpRequest, q: web::Json<TaskQuery>) -> HttpResponse {
-    let uuid: String = parse_uuid(req);
-    let mut task = read_task(&uuid).unwrap();
+async fn update_task(
+    req: HttpRequest,
+    q: web::Json<TaskQuery>,
+    store: web::Data<dyn Storage>,
+    config: web::Data<Config>,
+) -> EingangHttpResponse {
+    let uuid = parse_uuid(req)?;
+    let mut task = store.get_task(&uuid)?;
     let tq = q.into_inner();
 
     let mut task_changed = false;
@@ -95,17 +261,147 @@ async fn update_task(req: HttpRequest, q: web::Json<TaskQuery>) -> HttpResponse
         task.title = t;
         task_changed = true;
     }
+    if let Some(t) = tq.tags {
+        task.tags = normalize_tags(t);
+        task_changed = true;
+    }
+    if let Some(due) = tq.due {
+        task.due = parse_date("due", &due, true, &config.time_zone)?;
+        task_changed = true;
+    }
+    if let Some(scheduled) = tq.scheduled {
+        task.scheduled = parse_date("scheduled", &scheduled, false, &config.time_zone)?;
+        task_changed = true;
+    }
+    if let Some(recurrence) = tq.recurrence {
+        task.recurrence = parse_recurrence(&recurrence)?;
+        task_changed = true;
+    }
+    if let Some(blockers) = tq.blocked_by {
+        set_blockers(&mut task, blockers, &**store)?;
+        task_changed = true;
+    }
+    if let Some(priority) = tq.priority {
+        task.priority = parse_priority(&priority)?;
+        task_changed = true;
+    }
+    let was_closed = task.status == TaskStatus::Closed;
     if let Some(s) = tq.status {
-        match TaskStatus::from(s) {
-            Some(c) => task.status = c,
-            _ => return HttpResponse::BadRequest().json("Unknown status"),
-        }
+        let status = parse_status(s)?;
+        task.set_status(status).map_err(EingangError::Conflict)?;
         task_changed = true;
     }
 
+    // Closing a recurring task creates the next instance of the series, but
+    // closing it again after reopening does not create another one
+    if !was_closed && task.status == TaskStatus::Closed && task.successor.is_none() {
+        let now = chrono::Utc::now().with_timezone(&config.time_zone);
+        if let Some(next) = task.next_instance(now) {
+            store.save_task(&next)?;
+            task.successor = Some(next.get_uuid());
+        }
+    }
+
     if task_changed {
         task.update_modified_date();
-        save_task(&task);
+        store.save_task(&task)?;
+    }
+    Ok(HttpResponse::NoContent().json("Successful"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::io::{MemoryStorage, Storage};
+    use actix_web::{test, web, App};
+    use eingang::models::{ErrorResponse, Idable, Task, TaskQuery, TaskStatus};
+    use std::sync::Arc;
+
+    #[actix_rt::test]
+    async fn reopened_tasks_create_their_next_instance_once() {
+        let store: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let mut task = Task::new("Water the plants".to_string());
+        task.recurrence = Some("FREQ=WEEKLY".parse().unwrap());
+        store.save_task(&task).unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store.clone()))
+                .app_data(web::Data::new(Config::default()))
+                .configure(super::config),
+        )
+        .await;
+
+        for status in &["closed", "open", "closed"] {
+            let query = TaskQuery {
+                status: Some(status.to_string()),
+                ..Default::default()
+            };
+            let req = test::TestRequest::patch()
+                .uri(&format!("/tasks/{}/update", task.get_uuid()))
+                .set_json(&query)
+                .to_request();
+            test::call_service(&mut app, req).await;
+        }
+        let tasks = store.list_tasks().unwrap();
+        assert_eq!(tasks.len(), 2);
+        let successor = store.get_task(&task.get_uuid()).unwrap().successor;
+        let next = tasks
+            .iter()
+            .find(|t| t.get_uuid() != task.get_uuid())
+            .unwrap();
+        assert_eq!(successor, Some(next.get_uuid()));
+        assert_eq!(next.series, Some(task.get_uuid()));
+    }
+
+    #[actix_rt::test]
+    async fn huge_intervals_are_rejected() {
+        let store: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let mut app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store.clone()))
+                .app_data(web::Data::new(Config::default()))
+                .configure(super::config),
+        )
+        .await;
+
+        let query = TaskQuery {
+            content: Some("Water the plants".to_string()),
+            recurrence: Some("FREQ=WEEKLY;INTERVAL=4294967295;BYDAY=MO".to_string()),
+            ..Default::default()
+        };
+        let req = test::TestRequest::post()
+            .uri("/tasks/new")
+            .set_json(&query)
+            .to_request();
+        let error: ErrorResponse = test::read_response_json(&mut app, req).await;
+        assert_eq!(error.status, 400);
+        assert!(store.list_tasks().unwrap().is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn tasks_created_as_closed_record_their_closing() {
+        let store: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let mut app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store.clone()))
+                .app_data(web::Data::new(Config::default()))
+                .configure(super::config),
+        )
+        .await;
+
+        let query = TaskQuery {
+            content: Some("Already done".to_string()),
+            status: Some("closed".to_string()),
+            ..Default::default()
+        };
+        let req = test::TestRequest::post()
+            .uri("/tasks/new")
+            .set_json(&query)
+            .to_request();
+        let uuid: String = test::read_response_json(&mut app, req).await;
+        let task = store.get_task(&uuid.parse().unwrap()).unwrap();
+        assert_eq!(task.status, TaskStatus::Closed);
+        assert!(task.closed_on().is_some());
+        assert_eq!(task.history.len(), 1);
     }
-    HttpResponse::NoContent().json("Successful")
 }
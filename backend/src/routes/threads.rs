@@ -6,167 +6,685 @@
 //!
 //! ## Threads
 //! Threads
-//! - `/threads`: Returns a list of all threads
+//! Threads may contain other threads, as long as no thread ends up being part
+//! of itself. Threads with a saved search (`query`) are dynamic: their notes
+//! and tasks are the elements matching the query whenever the thread is read,
+//! and they cannot be changed by hand (409).
+//! - `/threads`: Returns a list of all active threads
 //!   - `filter=tasks`: Return all tasks from any thread
 //!   - `filter=notes`: Return all notes from any thread
+//!   - `recursive=true`: Let the filters include the elements of sub-threads
+//!   - `expand=true`: Resolve the notes, tasks and sub-threads of each thread
+//!   - `timeline=true`: Order resolved elements by their last modification
+//!   - `archived=true`: Return archived threads as well
 //! - `/threads/new`: Create new thread
+//!   - `title`, `description`: Name and description of the thread
 //!   - `tasks=[uuid]`: Vector of tasks to add for thread
 //!   - `notes=[uuid]`: Vector of notes to add for thread
+//!   - `threads=[uuid]`: Vector of sub-threads to add for thread
+//!   - `query`: Saved search instead of elements, e.g.
+//!     `type:task status:waiting untouched:2w`, dates without offset are local
+//!     to the configured `time_zone`
 //! - `/threads/{uuid}`: Returns a specific thread
 //!   - `filter=tasks`: Return only tasks from a specific thread
 //!   - `filter=notes`: Return only notes from a specific thread
-//! - `/threads/{uuid}/new`: Edit a specific note
+//!   - `recursive=true`: Let the filters include the elements of sub-threads
+//!   - `expand=true`: Resolve the notes, tasks and sub-threads of the thread,
+//!     elements which do not exist are returned as `missing`
+//!   - `timeline=true`: Order resolved elements by their last modification
+//!   - `depth=n`: Return the thread with its sub-threads expanded `n` levels
+//!
+//!   Filters take precedence over `expand`, which takes precedence over
+//!   `depth`.
+//! - `/threads/slug/{slug}`: Returns the thread whose title has this slug
+//! - `/threads/{uuid}/update`: Edit `title`, `description`, `state`
+//!   (`active` or `archived`) or `query` of a thread, an empty `query` turns
+//!   it back into a static, empty thread
+//! - `/threads/{uuid}/add`: Add an element to a thread
 //!   - `task={uuid}`: Add task to thread
 //!   - `note={uuid}`: Add note to thread
+//!   - `thread={uuid}`: Add sub-thread to thread, cycles are rejected
+//!   - `position=n`: Insert the element at position `n` instead of the end
+//! - `/threads/{uuid}/reorder`: Change the order of the elements, either
+//!   with the complete new order `items` or by moving the element at `from`
+//!   to position `to`
 //! - `/threads/{uuid}/delete`: Delete whole thread
 //!   - `task={uuid}`: Delete a task from thread
 //!   - `note={uuid}`: Delete a note from thread
-use super::{parse_uuid, EingangResponse, EingangVecResponse};
-use crate::io::{get_all_threads as gat, read_note, read_task};
-use crate::io::{read_thread, save_thread, Location};
+//!   - `thread={uuid}`: Delete a sub-thread from thread
+use super::{parse_uuid, EingangHttpResponse, EingangResponse, EingangVecResponse};
+use crate::config::Config;
+use crate::io::{self, fsck, query, EingangError, Storage};
 use actix_web::{web, HttpRequest, HttpResponse};
-use eingang::models::{Idable, NoteUuid, TaskUuid};
-use eingang::models::{Thread, ThreadFilter, ThreadQuery, ThreadResponse};
+use chrono_tz::Tz;
+use eingang::models::eingang::tree::{find_thread_cycle, flatten};
+use eingang::models::{slugify, Idable, NoteUuid, TaskUuid, ThreadNode, ThreadUuid};
+use eingang::models::{ExpandedItem, ExpandedThread, ReorderQuery, Thread, ThreadFilter};
+use eingang::models::{Query, ThreadItem, ThreadQuery, ThreadResponse};
+use std::collections::HashMap;
 
 /// Configure routes for Threads
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("/threads").route(web::get().to(get_all_threads)));
     cfg.service(web::resource("/threads/new").route(web::post().to(create_new_thread)));
+    cfg.service(web::resource("/threads/slug/{slug}").route(web::get().to(get_thread_by_slug)));
     cfg.service(web::resource("/threads/{uuid}").route(web::get().to(get_thread)));
     cfg.service(web::resource("/threads/{uuid}/delete").route(web::delete().to(delete_thread)));
     cfg.service(web::resource("/threads/{uuid}/add").route(web::patch().to(extend_thread)));
+    cfg.service(web::resource("/threads/{uuid}/update").route(web::patch().to(update_thread)));
+    cfg.service(web::resource("/threads/{uuid}/reorder").route(web::patch().to(reorder_thread)));
 }
 
 async fn get_all_threads(
     _: HttpRequest,
     q: web::Query<ThreadQuery>,
+    store: web::Data<dyn Storage>,
 ) -> EingangVecResponse<ThreadResponse> {
-    let result = gat().unwrap();
     let query = q.into_inner();
-    let r = if query.filter.is_some() {
-        match query.filter.unwrap() {
-            ThreadFilter::Tasks => result
-                .into_iter()
-                .map(|f| ThreadResponse::Tasks(f.tasks))
-                .collect(),
-            ThreadFilter::Notes => result
-                .into_iter()
-                .map(|f| ThreadResponse::Notes(f.notes))
-                .collect(),
-        }
+    let archived = query.archived.unwrap_or_default();
+    let threads = thread_map(&**store)?;
+    let mut result: Vec<&Thread> = threads
+        .values()
+        .filter(|t| archived || !t.is_archived())
+        .collect();
+    result.sort_by_key(|t| (t.created_on(), t.get_uuid()));
+    let r = result
+        .into_iter()
+        .map(|t| respond(t.clone(), &query, &threads, &**store))
+        .collect::<Result<_, EingangError>>()?;
+    Ok(web::Json(r))
+}
+
+/// All threads by UUID, with the items of saved searches resolved
+fn thread_map(store: &dyn Storage) -> Result<HashMap<ThreadUuid, Thread>, EingangError> {
+    let mut threads = store.list_threads()?;
+    for thread in threads.iter_mut() {
+        query::resolve_search(store, thread)?;
+    }
+    Ok(threads.into_iter().map(|t| (t.get_uuid(), t)).collect())
+}
+
+/// Parse the saved search of a thread, where an empty value removes it
+fn parse_query(value: &str, tz: &Tz) -> Result<Option<Query>, EingangError> {
+    if value.trim().is_empty() {
+        return Ok(None);
+    }
+    Query::parse_in(value, tz)
+        .map(Some)
+        .map_err(EingangError::BadRequest)
+}
+
+/// Fail for saved searches, whose items cannot be changed by hand
+fn ensure_static(thread: &Thread) -> Result<(), EingangError> {
+    if thread.is_search() {
+        return Err(EingangError::Conflict(format!(
+            "Thread {} is a saved search, its elements are given by its query",
+            thread.get_uuid()
+        )));
+    }
+    Ok(())
+}
+
+/// Return the notes or tasks of the thread, with `recursive` including all
+/// sub-threads, or the thread itself with resolved elements or expanded for
+/// `depth` levels
+fn respond(
+    thread: Thread,
+    query: &ThreadQuery,
+    threads: &HashMap<ThreadUuid, Thread>,
+    store: &dyn Storage,
+) -> Result<ThreadResponse, EingangError> {
+    let items = if query.recursive.unwrap_or_default() {
+        flatten(&thread, threads)
     } else {
-        result
-            .into_iter()
-            .map(|f| ThreadResponse::Threads(f))
-            .collect()
+        thread.items.clone()
     };
-    Ok(web::Json(r))
+    let select = |f: fn(&ThreadItem) -> Option<uuid::Uuid>| items.iter().filter_map(f).collect();
+    let r = match (query.filter, query.depth) {
+        (Some(ThreadFilter::Tasks), _) => ThreadResponse::Tasks(select(|i| match i {
+            ThreadItem::Task(uuid) => Some(*uuid),
+            _ => None,
+        })),
+        (Some(ThreadFilter::Notes), _) => ThreadResponse::Notes(select(|i| match i {
+            ThreadItem::Note(uuid) => Some(*uuid),
+            _ => None,
+        })),
+        (None, _) if query.expand.unwrap_or_default() => {
+            let items = items
+                .iter()
+                .map(|i| resolve(store, *i))
+                .collect::<Result<_, EingangError>>()?;
+            let expanded = ExpandedThread { thread, items };
+            if query.timeline.unwrap_or_default() {
+                ThreadResponse::Expanded(expanded.into_timeline())
+            } else {
+                ThreadResponse::Expanded(expanded)
+            }
+        }
+        (None, Some(depth)) => ThreadResponse::Tree(ThreadNode::new(thread, threads, depth)),
+        (None, None) => ThreadResponse::Threads(thread),
+    };
+    Ok(r)
 }
 
-async fn create_new_thread(q: web::Json<ThreadQuery>) -> HttpResponse {
-    // TODO breaks when UUID is not valid, should return BadRequest
+/// Load the element referenced by a thread, which may not exist anymore
+fn resolve(store: &dyn Storage, item: ThreadItem) -> Result<ExpandedItem, EingangError> {
+    let element = match item {
+        ThreadItem::Note(uuid) => store.get_note(&uuid).map(ExpandedItem::Note),
+        ThreadItem::Task(uuid) => store.get_task(&uuid).map(ExpandedItem::Task),
+        ThreadItem::Thread(uuid) => store.get_thread(&uuid).map(ExpandedItem::Thread),
+    };
+    match element {
+        Err(EingangError::NotFound(_)) => Ok(ExpandedItem::Missing { item }),
+        e => e,
+    }
+}
+
+async fn create_new_thread(
+    q: web::Json<ThreadQuery>,
+    store: web::Data<dyn Storage>,
+    config: web::Data<Config>,
+) -> EingangHttpResponse {
     let tq = q.into_inner();
     let tasks: Vec<TaskUuid> = tq
         .tasks
         .unwrap_or_default()
         .iter()
-        .filter_map(|uuid| read_task(&uuid).ok())
-        .map(|uuid| uuid.get_uuid())
-        .collect();
+        .map(|uuid| Ok(store.get_task(&io::parse_uuid(uuid)?)?.get_uuid()))
+        .collect::<Result<_, EingangError>>()?;
     let notes: Vec<NoteUuid> = tq
         .notes
         .unwrap_or_default()
         .iter()
-        .filter_map(|uuid| read_note(&uuid).ok())
-        .map(|uuid| uuid.get_uuid())
-        .collect();
-    let thread = Thread::with_tasks_and_notes(tasks, notes);
-    save_thread(&thread);
-    HttpResponse::Ok().json(thread)
+        .map(|uuid| Ok(store.get_note(&io::parse_uuid(uuid)?)?.get_uuid()))
+        .collect::<Result<_, EingangError>>()?;
+    let threads: Vec<ThreadUuid> = tq
+        .threads
+        .unwrap_or_default()
+        .iter()
+        .map(|uuid| Ok(store.get_thread(&io::parse_uuid(uuid)?)?.get_uuid()))
+        .collect::<Result<_, EingangError>>()?;
+    let mut thread = Thread::with_tasks_and_notes(tasks, notes);
+    thread
+        .items
+        .extend(threads.into_iter().map(ThreadItem::Thread));
+    if let Some(query) = tq.query {
+        if !thread.items.is_empty() {
+            return Err(EingangError::BadRequest(
+                "Either give a query OR tasks, notes and threads".into(),
+            ));
+        }
+        thread.query = parse_query(&query, &config.time_zone)?;
+    }
+    thread.title = tq.title.unwrap_or_default();
+    thread.description = tq.description.unwrap_or_default();
+    thread.state = tq.state.unwrap_or_default();
+    store.save_thread(&thread)?;
+    Ok(HttpResponse::Ok().json(thread))
 }
 
 async fn get_thread(
     req: HttpRequest,
     q: web::Query<ThreadQuery>,
+    store: web::Data<dyn Storage>,
 ) -> EingangResponse<ThreadResponse> {
-    let uuid: String = parse_uuid(req);
-    let thread = read_thread(&uuid).unwrap();
+    let uuid = parse_uuid(req)?;
+    let mut thread = store.get_thread(&uuid)?;
+    query::resolve_search(&**store, &mut thread)?;
     let query = q.into_inner();
-    let r = if query.filter.is_some() {
-        match query.filter.unwrap() {
-            ThreadFilter::Tasks => ThreadResponse::Tasks(thread.tasks),
-            ThreadFilter::Notes => ThreadResponse::Notes(thread.notes),
-        }
+    let threads = if query.depth.is_some() || query.recursive.unwrap_or_default() {
+        thread_map(&**store)?
     } else {
-        ThreadResponse::Threads(thread)
+        HashMap::new()
     };
-    Ok(web::Json(r))
+    Ok(web::Json(respond(thread, &query, &threads, &**store)?))
 }
 
-async fn delete_thread(req: HttpRequest, q: web::Query<ThreadQuery>) -> HttpResponse {
-    let uuid: String = parse_uuid(req);
+/// Return the thread whose title has the slug. Threads with titles differing
+/// only in punctuation or case share a slug, which is reported as conflict.
+async fn get_thread_by_slug(
+    req: HttpRequest,
+    store: web::Data<dyn Storage>,
+) -> EingangResponse<Thread> {
+    let slug = slugify(req.match_info().get("slug").unwrap_or_default());
+    let mut threads: Vec<Thread> = store
+        .list_threads()?
+        .into_iter()
+        .filter(|t| !slug.is_empty() && t.slug() == slug)
+        .collect();
+    match threads.len() {
+        0 => Err(EingangError::NotFound(format!(
+            "Thread with slug '{}'",
+            slug
+        ))),
+        1 => {
+            let mut thread = threads.remove(0);
+            query::resolve_search(&**store, &mut thread)?;
+            Ok(web::Json(thread))
+        }
+        _ => {
+            let uuids: Vec<String> = threads.iter().map(|t| t.get_uuid().to_string()).collect();
+            Err(EingangError::Conflict(format!(
+                "Slug '{}' matches the threads {}",
+                slug,
+                uuids.join(", ")
+            )))
+        }
+    }
+}
+
+async fn update_thread(
+    req: HttpRequest,
+    q: web::Json<ThreadQuery>,
+    store: web::Data<dyn Storage>,
+    config: web::Data<Config>,
+) -> EingangHttpResponse {
+    let uuid = parse_uuid(req)?;
+    let mut thread = store.get_thread(&uuid)?;
+    let tq = q.into_inner();
+
+    let mut thread_changed = false;
+    if let Some(t) = tq.title {
+        thread.title = t;
+        thread_changed = true;
+    }
+    if let Some(d) = tq.description {
+        thread.description = d;
+        thread_changed = true;
+    }
+    if let Some(s) = tq.state {
+        thread.state = s;
+        thread_changed = true;
+    }
+    if let Some(q) = tq.query {
+        let query = parse_query(&q, &config.time_zone)?;
+        if query.is_some() && !thread.items.is_empty() {
+            return Err(EingangError::Conflict(format!(
+                "Thread {} has elements, remove them before saving a search",
+                uuid
+            )));
+        }
+        thread.query = query;
+        thread_changed = true;
+    }
+    if thread_changed {
+        thread.update_modified_date();
+        store.save_thread(&thread)?;
+    }
+    Ok(HttpResponse::NoContent().json("Successful"))
+}
+
+async fn delete_thread(
+    req: HttpRequest,
+    q: web::Query<ThreadQuery>,
+    store: web::Data<dyn Storage>,
+) -> EingangHttpResponse {
+    let uuid = parse_uuid(req)?;
     let query = q.into_inner();
 
-    if query.task.is_some() && query.note.is_some() {
-        return HttpResponse::BadRequest().json("Either remove task or note from Thread");
-    }
-
-    // TODO Move deletion in `eingang-base`
-    if query.task.is_some() {
-        let mut thread = read_thread(&uuid).unwrap();
-        let task_uuid = query.task.unwrap();
-        let pos = thread.tasks.iter().position(|t| t.to_string() == task_uuid);
-        match pos {
-            Some(p) => {
-                thread.tasks.remove(p);
-                thread.update_modified_date();
-                save_thread(&thread);
-                HttpResponse::NoContent().json("Successful")
-            }
-            None => HttpResponse::BadRequest().json("Task UUID is not associated"),
+    let item = match single_item(&query)? {
+        Some(item) => item,
+        None => {
+            store.delete_thread(&uuid)?;
+            fsck::detach(&**store, &uuid)?;
+            return Ok(HttpResponse::NoContent().json("Successful"));
         }
-    } else if query.note.is_some() {
-        let mut thread = read_thread(&uuid).unwrap();
-        let note_uuid = query.note.unwrap();
-        let pos = thread.notes.iter().position(|n| n.to_string() == note_uuid);
-        match pos {
-            Some(p) => {
-                thread.notes.remove(p);
-                thread.update_modified_date();
-                save_thread(&thread);
-                HttpResponse::NoContent().json("Successful")
+    };
+
+    let mut thread = store.get_thread(&uuid)?;
+    ensure_static(&thread)?;
+    if !thread.remove(item) {
+        return Err(EingangError::NotFound(format!(
+            "{} {} in thread {}",
+            item.kind(),
+            item.uuid(),
+            uuid
+        )));
+    }
+    store.save_thread(&thread)?;
+    Ok(HttpResponse::NoContent().json("Successful"))
+}
+
+async fn extend_thread(
+    req: HttpRequest,
+    q: web::Query<ThreadQuery>,
+    store: web::Data<dyn Storage>,
+) -> EingangHttpResponse {
+    let uuid = parse_uuid(req)?;
+    let mut thread = store.get_thread(&uuid)?;
+    ensure_static(&thread)?;
+    let query = q.into_inner();
+
+    let item = match single_item(&query)? {
+        Some(ThreadItem::Task(task)) => ThreadItem::Task(store.get_task(&task)?.get_uuid()),
+        Some(ThreadItem::Note(note)) => ThreadItem::Note(store.get_note(&note)?.get_uuid()),
+        Some(ThreadItem::Thread(sub)) => {
+            let threads = thread_map(&**store)?;
+            if !threads.contains_key(&sub) {
+                return Err(EingangError::NotFound(format!("Thread {}", sub)));
             }
-            None => HttpResponse::BadRequest().json("Note UUID is not associated"),
+            if let Some(cycle) = find_thread_cycle(uuid, sub, &threads) {
+                let cycle: Vec<String> = cycle.iter().map(|u| u.to_string()).collect();
+                return Err(EingangError::Conflict(format!(
+                    "Thread cycle: {}",
+                    cycle.join(" -> ")
+                )));
+            }
+            ThreadItem::Thread(sub)
         }
-    } else {
-        let file = Location::Thread.create_filename(&uuid);
-        match std::fs::remove_file(file) {
-            Ok(_) => HttpResponse::NoContent().json("Successful"),
-            _ => HttpResponse::BadRequest().json("UUID is not associated"),
+        None => {
+            return Err(EingangError::BadRequest(
+                "No task, note or thread given to add".into(),
+            ))
         }
+    };
+    let message = format!("{} added", item.kind());
+    match query.position {
+        Some(p) => thread.insert(p, item).map_err(EingangError::BadRequest)?,
+        None => thread.push(item),
     }
+    store.save_thread(&thread)?;
+    Ok(HttpResponse::NoContent().json(message))
 }
 
-async fn extend_thread(req: HttpRequest, q: web::Query<ThreadQuery>) -> HttpResponse {
-    let uuid: String = parse_uuid(req);
-    let mut thread = read_thread(&uuid).unwrap();
+/// Return the task, note or sub-thread given in the query, if any
+fn single_item(query: &ThreadQuery) -> Result<Option<ThreadItem>, EingangError> {
+    let mut items = Vec::new();
+    if let Some(task) = &query.task {
+        items.push(ThreadItem::Task(io::parse_uuid(task)?));
+    }
+    if let Some(note) = &query.note {
+        items.push(ThreadItem::Note(io::parse_uuid(note)?));
+    }
+    if let Some(thread) = &query.thread {
+        items.push(ThreadItem::Thread(io::parse_uuid(thread)?));
+    }
+    if items.len() > 1 {
+        return Err(EingangError::BadRequest(
+            "Either give task, note or thread".into(),
+        ));
+    }
+    Ok(items.pop())
+}
+
+async fn reorder_thread(
+    req: HttpRequest,
+    q: web::Json<ReorderQuery>,
+    store: web::Data<dyn Storage>,
+) -> EingangHttpResponse {
+    let uuid = parse_uuid(req)?;
+    let mut thread = store.get_thread(&uuid)?;
+    ensure_static(&thread)?;
     let query = q.into_inner();
 
-    if query.task.is_some() && query.note.is_some() {
-        return HttpResponse::BadRequest().json("Either remove task or note");
+    let result = match (query.items, query.from, query.to) {
+        (Some(items), None, None) => thread.reorder(items),
+        (None, Some(from), Some(to)) => thread.move_item(from, to),
+        _ => {
+            return Err(EingangError::BadRequest(
+                "Either give the new order of all items OR move from one position to another"
+                    .into(),
+            ))
+        }
+    };
+    result.map_err(EingangError::BadRequest)?;
+    store.save_thread(&thread)?;
+    Ok(HttpResponse::NoContent().json("Successful"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::io::{MemoryStorage, Storage};
+    use actix_web::{test, web, App};
+    use eingang::models::{ErrorResponse, ExpandedItem, Idable, Note, ReorderQuery, Task, Thread};
+    use eingang::models::{TaskStatus, ThreadItem, ThreadQuery, ThreadResponse, ThreadState};
+    use std::sync::Arc;
+
+    #[actix_rt::test]
+    async fn archived_threads_are_hidden() {
+        let store: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let mut thread = Thread::new();
+        thread.title = "Umzug nach Köln".to_string();
+        store.save_thread(&thread).unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store.clone()))
+                .app_data(web::Data::new(Config::default()))
+                .configure(super::config),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/threads/slug/umzug-nach-koeln")
+            .to_request();
+        let found: Thread = test::read_response_json(&mut app, req).await;
+        assert_eq!(found.get_uuid(), thread.get_uuid());
+
+        let query = ThreadQuery {
+            state: Some(ThreadState::Archived),
+            ..Default::default()
+        };
+        let req = test::TestRequest::patch()
+            .uri(&format!("/threads/{}/update", thread.get_uuid()))
+            .set_json(&query)
+            .to_request();
+        test::call_service(&mut app, req).await;
+
+        let req = test::TestRequest::get().uri("/threads").to_request();
+        let threads: Vec<ThreadResponse> = test::read_response_json(&mut app, req).await;
+        assert!(threads.is_empty());
+        let req = test::TestRequest::get()
+            .uri("/threads?archived=true")
+            .to_request();
+        let threads: Vec<ThreadResponse> = test::read_response_json(&mut app, req).await;
+        assert_eq!(threads.len(), 1);
     }
 
-    if query.task.is_some() {
-        let task = read_task(&query.task.unwrap()).unwrap();
-        thread.add_task(task.get_uuid());
-        save_thread(&thread);
-        return HttpResponse::NoContent().json("Task added");
-    } else if query.note.is_some() {
-        let note = read_note(&query.note.unwrap()).unwrap();
-        thread.add_note(note.get_uuid());
-        save_thread(&thread);
-        return HttpResponse::NoContent().json("Note added");
-    } else {
-        HttpResponse::BadRequest().json("No task or note given to add")
+    #[actix_rt::test]
+    async fn reorder_items_of_thread() {
+        let store: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let note = Note::new("note".to_string());
+        let task = Task::new("task".to_string());
+        store.save_note(&note).unwrap();
+        store.save_task(&task).unwrap();
+        let thread = Thread::with_notes(vec![note.get_uuid()]);
+        store.save_thread(&thread).unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store.clone()))
+                .app_data(web::Data::new(Config::default()))
+                .configure(super::config),
+        )
+        .await;
+
+        let req = test::TestRequest::patch()
+            .uri(&format!(
+                "/threads/{}/add?task={}&position=0",
+                thread.get_uuid(),
+                task.get_uuid()
+            ))
+            .to_request();
+        test::call_service(&mut app, req).await;
+        let items = store.get_thread(&thread.get_uuid()).unwrap().items;
+        assert_eq!(items[0], ThreadItem::Task(task.get_uuid()));
+
+        let query = ReorderQuery {
+            from: Some(0),
+            to: Some(1),
+            ..Default::default()
+        };
+        let req = test::TestRequest::patch()
+            .uri(&format!("/threads/{}/reorder", thread.get_uuid()))
+            .set_json(&query)
+            .to_request();
+        test::call_service(&mut app, req).await;
+        let items = store.get_thread(&thread.get_uuid()).unwrap().items;
+        assert_eq!(
+            items,
+            vec![
+                ThreadItem::Note(note.get_uuid()),
+                ThreadItem::Task(task.get_uuid())
+            ]
+        );
+
+        let query = ReorderQuery {
+            items: Some(vec![ThreadItem::Note(note.get_uuid())]),
+            ..Default::default()
+        };
+        let req = test::TestRequest::patch()
+            .uri(&format!("/threads/{}/reorder", thread.get_uuid()))
+            .set_json(&query)
+            .to_request();
+        let error: ErrorResponse = test::read_response_json(&mut app, req).await;
+        assert_eq!(error.status, 400);
+    }
+
+    #[actix_rt::test]
+    async fn nested_threads_without_cycles() {
+        let store: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let task = Task::new("task".to_string());
+        store.save_task(&task).unwrap();
+        let child = Thread::with_tasks(vec![task.get_uuid()]);
+        let parent = Thread::new();
+        store.save_thread(&child).unwrap();
+        store.save_thread(&parent).unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store.clone()))
+                .app_data(web::Data::new(Config::default()))
+                .configure(super::config),
+        )
+        .await;
+
+        let add = |to: &Thread, sub: &Thread| {
+            test::TestRequest::patch()
+                .uri(&format!(
+                    "/threads/{}/add?thread={}",
+                    to.get_uuid(),
+                    sub.get_uuid()
+                ))
+                .to_request()
+        };
+        test::call_service(&mut app, add(&parent, &child)).await;
+        let error: ErrorResponse = test::read_response_json(&mut app, add(&child, &parent)).await;
+        assert_eq!(error.status, 409);
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/threads/{}?depth=1", parent.get_uuid()))
+            .to_request();
+        let tree: ThreadResponse = test::read_response_json(&mut app, req).await;
+        match tree {
+            ThreadResponse::Tree(node) => assert_eq!(node.threads.len(), 1),
+            r => panic!("Expected tree, got {:?}", r),
+        }
+
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/threads/{}?filter=tasks&recursive=true",
+                parent.get_uuid()
+            ))
+            .to_request();
+        let tasks: ThreadResponse = test::read_response_json(&mut app, req).await;
+        match tasks {
+            ThreadResponse::Tasks(tasks) => assert_eq!(tasks, vec![task.get_uuid()]),
+            r => panic!("Expected tasks, got {:?}", r),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn expanded_threads_report_missing_elements() {
+        let store: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let note = Note::new("note".to_string());
+        let missing = uuid::Uuid::new_v4();
+        store.save_note(&note).unwrap();
+        let thread = Thread::with_tasks_and_notes(vec![missing], vec![note.get_uuid()]);
+        store.save_thread(&thread).unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store.clone()))
+                .app_data(web::Data::new(Config::default()))
+                .configure(super::config),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/threads/{}?expand=true", thread.get_uuid()))
+            .to_request();
+        let expanded: ThreadResponse = test::read_response_json(&mut app, req).await;
+        let expanded = match expanded {
+            ThreadResponse::Expanded(e) => e,
+            r => panic!("Expected expanded thread, got {:?}", r),
+        };
+        assert_eq!(expanded.missing(), vec![ThreadItem::Task(missing)]);
+        match &expanded.items[1] {
+            ExpandedItem::Note(n) => assert_eq!(n.content, "note"),
+            i => panic!("Expected note, got {:?}", i),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn saved_searches_resolve_their_elements() {
+        let store: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+        let waiting = Task::with_title_and_status(
+            "Reply from the landlord".to_string(),
+            String::new(),
+            TaskStatus::Waiting,
+        );
+        store.save_task(&waiting).unwrap();
+        store.save_task(&Task::new("open".to_string())).unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(store.clone()))
+                .app_data(web::Data::new(Config::default()))
+                .configure(super::config),
+        )
+        .await;
+
+        let query = ThreadQuery {
+            title: Some("Waiting".to_string()),
+            query: Some("type:task status:waiting".to_string()),
+            ..Default::default()
+        };
+        let req = test::TestRequest::post()
+            .uri("/threads/new")
+            .set_json(&query)
+            .to_request();
+        let thread: Thread = test::read_response_json(&mut app, req).await;
+        assert!(store
+            .get_thread(&thread.get_uuid())
+            .unwrap()
+            .items
+            .is_empty());
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/threads/{}?filter=tasks", thread.get_uuid()))
+            .to_request();
+        let tasks: ThreadResponse = test::read_response_json(&mut app, req).await;
+        match tasks {
+            ThreadResponse::Tasks(tasks) => assert_eq!(tasks, vec![waiting.get_uuid()]),
+            r => panic!("Expected tasks, got {:?}", r),
+        }
+
+        let req = test::TestRequest::patch()
+            .uri(&format!(
+                "/threads/{}/add?task={}",
+                thread.get_uuid(),
+                waiting.get_uuid()
+            ))
+            .to_request();
+        let error: ErrorResponse = test::read_response_json(&mut app, req).await;
+        assert_eq!(error.status, 409);
+
+        let query = ThreadQuery {
+            query: Some("status:nonsense".to_string()),
+            ..Default::default()
+        };
+        let req = test::TestRequest::patch()
+            .uri(&format!("/threads/{}/update", thread.get_uuid()))
+            .set_json(&query)
+            .to_request();
+        let error: ErrorResponse = test::read_response_json(&mut app, req).await;
+        assert_eq!(error.status, 400);
     }
 }
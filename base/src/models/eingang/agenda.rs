@@ -0,0 +1,115 @@
+use super::from_local;
+use super::task::Task;
+use chrono::{DateTime, Duration, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+
+/// Default number of days covered by the upcoming section of the agenda
+pub const UPCOMING_DAYS: u32 = 7;
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct AgendaQuery {
+    /// Number of days after today covered by `upcoming`
+    pub days: Option<u32>,
+}
+
+/// Active tasks ordered by their deadlines and start dates
+///
+/// A task is part of at most one of `overdue`, `today` and `upcoming`, but
+/// can also be part of `scheduled`. All lists are sorted by date.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Agenda {
+    /// Tasks whose deadline has passed
+    pub overdue: Vec<Task>,
+    /// Tasks due for the rest of today
+    pub today: Vec<Task>,
+    /// Tasks due within the next days
+    pub upcoming: Vec<Task>,
+    /// Tasks which are scheduled to start today or earlier
+    pub scheduled: Vec<Task>,
+}
+
+impl Agenda {
+    /// Sort the active tasks into the agenda for the moment `now`, whose time
+    /// zone defines the days
+    pub fn new<Tz: TimeZone>(tasks: Vec<Task>, now: DateTime<Tz>, days: u32) -> Self {
+        let tz = now.timezone();
+        let tomorrow = now.naive_local().date() + Duration::days(1);
+        let midnight = |date: NaiveDate| from_local(&tz, date.and_hms(0, 0, 0));
+        let start_of_tomorrow = midnight(tomorrow).unwrap_or(chrono::MAX_DATETIME);
+        let end_of_upcoming = tomorrow
+            .checked_add_signed(Duration::days(days as i64))
+            .and_then(midnight)
+            .unwrap_or(chrono::MAX_DATETIME);
+        let now = now.with_timezone(&chrono::Utc);
+
+        let mut agenda = Agenda::default();
+        for task in tasks.into_iter().filter(|t| t.status.is_active()) {
+            if task.scheduled.is_some_and(|s| s < start_of_tomorrow) {
+                agenda.scheduled.push(task.clone());
+            }
+            match task.due {
+                Some(due) if due < now => agenda.overdue.push(task),
+                Some(due) if due < start_of_tomorrow => agenda.today.push(task),
+                Some(due) if due < end_of_upcoming => agenda.upcoming.push(task),
+                _ => {}
+            }
+        }
+        agenda.overdue.sort_by_key(|t| t.due);
+        agenda.today.sort_by_key(|t| t.due);
+        agenda.upcoming.sort_by_key(|t| t.due);
+        agenda.scheduled.sort_by_key(|t| t.scheduled);
+        agenda
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Agenda;
+    use crate::models::eingang::parse_timestamp;
+    use crate::models::eingang::task::{Task, TaskStatus};
+    use chrono::TimeZone;
+
+    fn task(due: Option<&str>, scheduled: Option<&str>, status: TaskStatus) -> Task {
+        let mut task = Task::with_title_and_status(String::new(), String::new(), status);
+        task.due = due.and_then(|d| parse_timestamp(d, true));
+        task.scheduled = scheduled.and_then(|s| parse_timestamp(s, false));
+        task
+    }
+
+    #[test]
+    fn sort_tasks_into_agenda() {
+        let now = parse_timestamp("2021-03-10T12:00:00Z", false).unwrap();
+        let tasks = vec![
+            task(Some("2021-03-09"), None, TaskStatus::Open),
+            task(Some("2021-03-10"), Some("2021-03-10"), TaskStatus::Waiting),
+            task(Some("2021-03-12"), Some("2021-03-11"), TaskStatus::Open),
+            task(Some("2021-03-30"), None, TaskStatus::Open),
+            task(Some("2021-03-01"), None, TaskStatus::Closed),
+        ];
+        let agenda = Agenda::new(tasks, now, 7);
+        assert_eq!(agenda.overdue.len(), 1);
+        assert_eq!(agenda.today.len(), 1);
+        assert_eq!(agenda.upcoming.len(), 1);
+        assert_eq!(agenda.scheduled.len(), 1);
+        assert_eq!(agenda.scheduled[0].status, TaskStatus::Waiting);
+    }
+
+    #[test]
+    fn agenda_days_are_local() {
+        // 2021-03-10, 23:00 in UTC-5
+        let now = chrono::FixedOffset::west(5 * 3600)
+            .ymd(2021, 3, 10)
+            .and_hms(23, 0, 0);
+        let tasks = vec![
+            task(Some("2021-03-11T04:30:00Z"), None, TaskStatus::Open),
+            task(Some("2021-03-11T06:00:00Z"), None, TaskStatus::Open),
+        ];
+        let agenda = Agenda::new(tasks, now, 7);
+        assert_eq!(agenda.today.len(), 1);
+        assert_eq!(
+            agenda.today[0].due,
+            parse_timestamp("2021-03-11T04:30:00Z", false)
+        );
+        assert_eq!(agenda.upcoming.len(), 1);
+    }
+}
@@ -0,0 +1,136 @@
+//! Dependencies between tasks.
+//!
+//! A task is blocked by all tasks in its `blocked_by` list until they are
+//! closed or deactivated. The dependencies must not contain cycles.
+use super::task::{Task, TaskStatus};
+use super::thread::TaskUuid;
+use super::Idable;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct DependencyQuery {
+    /// Return the task together with the tasks blocking it
+    #[serde(default)]
+    pub dependencies: bool,
+}
+
+/// A task together with the complete chain of tasks blocking it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DependencyNode {
+    pub task: Task,
+    pub blocked_by: Vec<DependencyNode>,
+}
+
+impl DependencyNode {
+    /// Build the dependency chain of `task`. Blockers which do not exist are
+    /// left out.
+    pub fn new(task: Task, tasks: &HashMap<TaskUuid, Task>) -> Self {
+        let mut path = HashSet::new();
+        DependencyNode::build(task, tasks, &mut path)
+    }
+    fn build(task: Task, tasks: &HashMap<TaskUuid, Task>, path: &mut HashSet<TaskUuid>) -> Self {
+        path.insert(task.get_uuid());
+        let mut blocked_by = Vec::new();
+        for blocker in &task.blocked_by {
+            if path.contains(blocker) {
+                continue;
+            }
+            if let Some(b) = tasks.get(blocker) {
+                blocked_by.push(DependencyNode::build(b.clone(), tasks, path));
+            }
+        }
+        path.remove(&task.get_uuid());
+        DependencyNode { task, blocked_by }
+    }
+}
+
+/// Whether a task with this status still blocks other tasks
+pub fn is_blocking(status: TaskStatus) -> bool {
+    !matches!(status, TaskStatus::Closed | TaskStatus::Deactivated)
+}
+
+/// Return the cycle which would be created by letting `task` be blocked by
+/// `blocked_by`, starting and ending with `task`
+pub fn find_cycle(
+    task: TaskUuid,
+    blocked_by: &[TaskUuid],
+    tasks: &HashMap<TaskUuid, Task>,
+) -> Option<Vec<TaskUuid>> {
+    let mut visited = HashSet::new();
+    let mut stack: Vec<(TaskUuid, Vec<TaskUuid>)> =
+        blocked_by.iter().map(|b| (*b, vec![task, *b])).collect();
+    while let Some((current, path)) = stack.pop() {
+        if current == task {
+            return Some(path);
+        }
+        if !visited.insert(current) {
+            continue;
+        }
+        if let Some(t) = tasks.get(&current) {
+            for next in &t.blocked_by {
+                let mut next_path = path.clone();
+                next_path.push(*next);
+                stack.push((*next, next_path));
+            }
+        }
+    }
+    None
+}
+
+/// Return all open tasks which are not blocked by any other task
+pub fn next_actions(tasks: &HashMap<TaskUuid, Task>) -> Vec<Task> {
+    let mut next: Vec<Task> = tasks
+        .values()
+        .filter(|t| t.status == TaskStatus::Open)
+        .filter(|t| {
+            t.blocked_by
+                .iter()
+                .filter_map(|b| tasks.get(b))
+                .all(|b| !is_blocking(b.status))
+        })
+        .cloned()
+        .collect();
+    next.sort_by_key(|t| t.created_on());
+    next
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_cycle, next_actions, DependencyNode};
+    use crate::models::eingang::task::{Task, TaskStatus};
+    use crate::models::eingang::Idable;
+    use std::collections::HashMap;
+
+    #[test]
+    fn detect_cycles_and_next_actions() {
+        let mut a = Task::new("a".to_string());
+        let mut b = Task::new("b".to_string());
+        let c = Task::with_title_and_status(String::new(), String::new(), TaskStatus::Closed);
+        a.blocked_by = vec![b.get_uuid()];
+        b.blocked_by = vec![c.get_uuid()];
+        let tasks: HashMap<_, _> = vec![a.clone(), b.clone(), c.clone()]
+            .into_iter()
+            .map(|t| (t.get_uuid(), t))
+            .collect();
+
+        let cycle = find_cycle(c.get_uuid(), &[a.get_uuid()], &tasks).unwrap();
+        assert_eq!(
+            cycle,
+            vec![c.get_uuid(), a.get_uuid(), b.get_uuid(), c.get_uuid()]
+        );
+        assert!(find_cycle(a.get_uuid(), &[a.get_uuid()], &tasks).is_some());
+        assert!(find_cycle(c.get_uuid(), &[b.get_uuid()], &tasks).is_some());
+        assert!(find_cycle(a.get_uuid(), &[c.get_uuid()], &tasks).is_none());
+
+        let next = next_actions(&tasks);
+        assert_eq!(next.len(), 1);
+        assert_eq!(next[0].get_uuid(), b.get_uuid());
+
+        let chain = DependencyNode::new(a, &tasks);
+        assert_eq!(
+            chain.blocked_by[0].blocked_by[0].task.get_uuid(),
+            c.get_uuid()
+        );
+    }
+}
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+/// Kind of error reported by the backend
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    NotFound,
+    InvalidUuid,
+    BadRequest,
+    Conflict,
+    Corrupt,
+    Io,
+}
+
+/// The body of every error response of the backend.
+///
+/// ```json
+/// { "status": 404, "kind": "not_found", "message": "Note 5c5f... not found" }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorResponse {
+    pub status: u16,
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl ErrorResponse {
+    pub fn new(status: u16, kind: ErrorKind, message: String) -> Self {
+        ErrorResponse {
+            status,
+            kind,
+            message,
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}): {}",
+            self.status,
+            kind_name(self.kind),
+            self.message
+        )
+    }
+}
+
+fn kind_name(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::NotFound => "not found",
+        ErrorKind::InvalidUuid => "invalid uuid",
+        ErrorKind::BadRequest => "bad request",
+        ErrorKind::Conflict => "conflict",
+        ErrorKind::Corrupt => "corrupt",
+        ErrorKind::Io => "io",
+    }
+}
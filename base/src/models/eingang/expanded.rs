@@ -0,0 +1,84 @@
+//! Threads with their notes and tasks resolved inline.
+use super::note::Note;
+use super::task::Task;
+use super::thread::{Thread, ThreadItem};
+use super::Timestamp;
+use serde::{Deserialize, Serialize};
+
+/// Element of a thread resolved to the full note, task or sub-thread
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ExpandedItem {
+    Note(Note),
+    Task(Task),
+    Thread(Thread),
+    /// Element referenced by the thread which does not exist
+    Missing {
+        item: ThreadItem,
+    },
+}
+
+impl ExpandedItem {
+    /// Moment of the last change, unknown for missing elements
+    pub fn last_modified(&self) -> Option<Timestamp> {
+        match self {
+            ExpandedItem::Note(n) => Some(n.last_modified()),
+            ExpandedItem::Task(t) => Some(t.last_modified()),
+            ExpandedItem::Thread(t) => Some(t.last_modified()),
+            ExpandedItem::Missing { .. } => None,
+        }
+    }
+}
+
+/// A thread together with all its resolved elements
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExpandedThread {
+    pub thread: Thread,
+    pub items: Vec<ExpandedItem>,
+}
+
+impl ExpandedThread {
+    /// Order the elements as timeline, oldest change first. Missing elements
+    /// are put at the end.
+    pub fn into_timeline(mut self) -> Self {
+        self.items
+            .sort_by_key(|i| (i.last_modified().is_none(), i.last_modified()));
+        self
+    }
+    /// UUIDs of all elements referenced by the thread which do not exist
+    pub fn missing(&self) -> Vec<ThreadItem> {
+        self.items
+            .iter()
+            .filter_map(|i| match i {
+                ExpandedItem::Missing { item } => Some(*item),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExpandedItem, ExpandedThread};
+    use crate::models::eingang::{note::Note, task::Task, thread::Thread, thread::ThreadItem};
+
+    #[test]
+    fn timeline_puts_missing_elements_last() {
+        let missing = ThreadItem::Note(uuid::Uuid::new_v4());
+        let mut task = Task::new("task".to_string());
+        let note = Note::new("note".to_string());
+        task.update_modified_date();
+        let expanded = ExpandedThread {
+            thread: Thread::new(),
+            items: vec![
+                ExpandedItem::Task(task),
+                ExpandedItem::Missing { item: missing },
+                ExpandedItem::Note(note),
+            ],
+        };
+        let timeline = expanded.into_timeline();
+        assert!(matches!(timeline.items[0], ExpandedItem::Note(_)));
+        assert!(matches!(timeline.items[1], ExpandedItem::Task(_)));
+        assert_eq!(timeline.missing(), vec![missing]);
+    }
+}
@@ -0,0 +1,49 @@
+use super::thread::{NoteUuid, TaskUuid, ThreadUuid};
+use serde::{Deserialize, Serialize};
+
+/// Problem with the references between threads and their elements
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "problem", rename_all = "snake_case")]
+pub enum Problem {
+    /// A thread references a note which does not exist
+    DanglingNote { thread: uuid::Uuid, note: NoteUuid },
+    /// A thread references a task which does not exist
+    DanglingTask { thread: uuid::Uuid, task: TaskUuid },
+    /// A thread references a sub-thread which does not exist
+    DanglingThread {
+        thread: uuid::Uuid,
+        subthread: ThreadUuid,
+    },
+    /// A thread references the same note several times
+    DuplicateNote { thread: uuid::Uuid, note: NoteUuid },
+    /// A thread references the same task several times
+    DuplicateTask { thread: uuid::Uuid, task: TaskUuid },
+    /// A thread references the same sub-thread several times
+    DuplicateThread {
+        thread: uuid::Uuid,
+        subthread: ThreadUuid,
+    },
+    /// A note is not part of any thread
+    OrphanedNote { note: NoteUuid },
+    /// A task is not part of any thread
+    OrphanedTask { task: TaskUuid },
+}
+
+impl Problem {
+    /// Orphaned elements are only reported, all other problems can be
+    /// repaired by removing the reference from the thread
+    pub fn is_repairable(&self) -> bool {
+        !matches!(
+            self,
+            Problem::OrphanedNote { .. } | Problem::OrphanedTask { .. }
+        )
+    }
+}
+
+/// Result of checking the storage
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct FsckReport {
+    pub problems: Vec<Problem>,
+    /// Number of problems which were repaired
+    pub repaired: usize,
+}
@@ -1,62 +1,101 @@
-use super::Timestamp;
+use super::range::Range;
+use super::{add_months, from_local, Timestamp};
 use super::{note::Note, task::Task, thread::Thread};
-use chrono::NaiveDate;
+use chrono::{DateTime, Datelike, Duration, TimeZone};
 use serde::{Deserialize, Serialize};
-
-const TIME: &str = "%Y-%m-%d";
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct JournalQuery {
     pub after: Option<String>,
     pub before: Option<String>,
-    pub during: Option<Period>,
+    pub during: Option<During>,
     pub untouched: Option<Period>,
     pub filter: Option<JournalFilter>,
+    /// Only return notes and tasks with this tag or one of its descendants
+    pub tag: Option<String>,
+    /// Also return archived threads
+    pub archived: Option<bool>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum JournalFilter {
     Threads,
     Notes,
     Tasks,
+    #[default]
     All,
 }
 
-impl Default for JournalFilter {
-    fn default() -> Self {
-        JournalFilter::All
-    }
+/// Either a period up to now, e.g. `{"days": 7}`, or a range of time in
+/// words, e.g. `"last month"` (see [`Range`])
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum During {
+    Period(Period),
+    Range(String),
 }
 
+/// Bounds `(before, after)` of the modification date
+pub type Bounds = (Option<Timestamp>, Option<Timestamp>);
+
 impl JournalQuery {
-    pub fn after_to_timestamp(&self) -> Option<Timestamp> {
-        match &self.after {
-            Some(s) => match NaiveDate::parse_from_str(s.as_str(), TIME).ok() {
-                Some(s) => {
-                    let ndt = s.and_hms(23, 59, 59);
-                    Some(Timestamp::from_utc(ndt, chrono::Utc))
+    /// Bounds of the modification date given by the query. Like the
+    /// `list_*_between` functions of the storages, both bounds are excluded.
+    /// Days and moments without offset are those of the time zone of `now`.
+    pub fn bounds<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> Result<Bounds, String> {
+        if let Some(during) = &self.during {
+            return match during {
+                During::Period(period) => Ok((None, Some(period.before(now.clone())))),
+                During::Range(s) => {
+                    let range = parse_range("during", s, now)?;
+                    Ok((range.end, Some(range.start - Duration::nanoseconds(1))))
                 }
-                _ => None,
-            },
-            _ => None,
+            };
+        }
+        if let Some(untouched) = self.untouched {
+            return Ok((Some(untouched.before(now.clone())), None));
         }
+        Ok((
+            self.before_to_timestamp(now)?,
+            self.after_to_timestamp(now)?,
+        ))
     }
-    pub fn before_to_timestamp(&self) -> Option<Timestamp> {
+    /// Moment given by `after`: the start of a moment, date or range, so the
+    /// named day or range is included, e.g. `yesterday` or `3 days ago`
+    pub fn after_to_timestamp<Tz: TimeZone>(
+        &self,
+        now: &DateTime<Tz>,
+    ) -> Result<Option<Timestamp>, String> {
+        match &self.after {
+            Some(s) => Ok(Some(parse_range("after", s, now)?.start)),
+            None => Ok(None),
+        }
+    }
+    /// Moment given by `before`: the start of a moment, date or range, which
+    /// is excluded like everything after it
+    pub fn before_to_timestamp<Tz: TimeZone>(
+        &self,
+        now: &DateTime<Tz>,
+    ) -> Result<Option<Timestamp>, String> {
         match &self.before {
-            Some(s) => match NaiveDate::parse_from_str(s.as_str(), TIME).ok() {
-                Some(s) => {
-                    let ndt = s.and_hms(0, 0, 1);
-                    Some(Timestamp::from_utc(ndt, chrono::Utc))
-                }
-                _ => None,
-            },
-            _ => None,
+            Some(s) => Ok(Some(parse_range("before", s, now)?.start)),
+            None => Ok(None),
         }
     }
 }
 
-#[derive(Serialize, Default, Deserialize, Debug, Copy, Clone)]
+fn parse_range<Tz: TimeZone>(
+    field: &str,
+    value: &str,
+    now: &DateTime<Tz>,
+) -> Result<Range, String> {
+    Range::parse(value, now).map_err(|_| format!("Invalid date '{}' for field '{}'", value, field))
+}
+
+#[derive(Serialize, Default, Deserialize, Debug, Copy, Clone, PartialEq)]
 pub struct Period {
     years: Option<u32>,
     months: Option<u32>,
@@ -76,18 +115,228 @@ pub enum JournalResponse {
 }
 
 impl Period {
-    fn to_timedelta(&self) -> chrono::Duration {
-        chrono::Duration::days(self.years.unwrap_or_default() as i64 * 365)
-            + chrono::Duration::days(self.months.unwrap_or_default() as i64 * 30)
-            + chrono::Duration::weeks(self.weeks.unwrap_or_default() as i64)
-            + chrono::Duration::days(self.days.unwrap_or_default() as i64)
-            + chrono::Duration::hours(self.hours.unwrap_or_default() as i64)
-            + chrono::Duration::minutes(self.minutes.unwrap_or_default() as i64)
-            + chrono::Duration::seconds(self.seconds.unwrap_or_default() as i64)
+    /// Moment the period before now
+    pub fn to_timestamp(self) -> Timestamp {
+        self.before(chrono::Utc::now())
+    }
+
+    /// Moment the period before `moment`. Years and months are subtracted on
+    /// the calendar of the time zone of `moment`, where the day is clamped to
+    /// the length of the month (one month before March 31st is the last day
+    /// of February). Weeks and days are calendar days as well, hours, minutes
+    /// and seconds exact durations. Periods reaching before the earliest
+    /// representable moment end there.
+    pub fn before<Tz: TimeZone>(self, moment: DateTime<Tz>) -> Timestamp {
+        let months =
+            self.years.unwrap_or_default() as i64 * 12 + self.months.unwrap_or_default() as i64;
+        let days = self.weeks.unwrap_or_default() as i64 * 7 + self.days.unwrap_or_default() as i64;
+        let local = moment.naive_local();
+        let start = add_months(local.date(), -months, local.day())
+            .and_then(|d| d.checked_sub_signed(Duration::days(days)))
+            .and_then(|d| from_local(&moment.timezone(), d.and_time(local.time())));
+        let start = match start {
+            Some(start) => start,
+            None => return chrono::MIN_DATETIME,
+        };
+        let exact = Duration::seconds(
+            self.hours.unwrap_or_default() as i64 * 3600
+                + self.minutes.unwrap_or_default() as i64 * 60
+                + self.seconds.unwrap_or_default() as i64,
+        );
+        start
+            .checked_sub_signed(exact)
+            .unwrap_or(chrono::MIN_DATETIME)
+    }
+
+    fn fields(&self) -> [Option<u32>; 7] {
+        [
+            self.years,
+            self.months,
+            self.weeks,
+            self.days,
+            self.hours,
+            self.minutes,
+            self.seconds,
+        ]
+    }
+}
+
+/// Units of the short form of periods, largest first
+const UNITS: [&str; 7] = ["y", "mo", "w", "d", "h", "min", "s"];
+
+/// Parse the short form of a period, e.g. `2w` or `1y6mo`, with the units
+/// `y`, `mo`, `w`, `d`, `h`, `min` and `s`
+impl FromStr for Period {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid period '{}', expected e.g. '2w' or '1y6mo'", s);
+        let mut period = Period::default();
+        let mut rest = s.trim();
+        if rest.is_empty() {
+            return Err(invalid());
+        }
+        while !rest.is_empty() {
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .ok_or_else(invalid)?;
+            let value: u32 = rest[..digits].parse().map_err(|_| invalid())?;
+            rest = &rest[digits..];
+            let unit = rest
+                .find(|c: char| c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let field = match &rest[..unit] {
+                "y" => &mut period.years,
+                "mo" => &mut period.months,
+                "w" => &mut period.weeks,
+                "d" => &mut period.days,
+                "h" => &mut period.hours,
+                "min" => &mut period.minutes,
+                "s" => &mut period.seconds,
+                _ => return Err(invalid()),
+            };
+            if field.is_some() {
+                return Err(invalid());
+            }
+            *field = Some(value);
+            rest = &rest[unit..];
+        }
+        Ok(period)
+    }
+}
+
+impl Display for Period {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut empty = true;
+        for (value, unit) in self.fields().iter().zip(UNITS.iter()) {
+            if let Some(value) = value {
+                write!(f, "{}{}", value, unit)?;
+                empty = false;
+            }
+        }
+        if empty {
+            write!(f, "0s")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{During, JournalQuery, Period};
+    use crate::models::eingang::parse_timestamp;
+    use chrono::TimeZone;
+
+    #[test]
+    fn parse_and_print_periods() {
+        let period: Period = "1y6mo".parse().unwrap();
+        assert_eq!(
+            period,
+            Period {
+                years: Some(1),
+                months: Some(6),
+                ..Default::default()
+            }
+        );
+        assert_eq!(period.to_string(), "1y6mo");
+        assert_eq!("2w3d".parse::<Period>().unwrap().to_string(), "2w3d");
+        for invalid in &["", "w", "2", "2x", "1d1d", "-1d"] {
+            assert!(invalid.parse::<Period>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn periods_follow_the_calendar() {
+        let moment = |s| parse_timestamp(s, false).unwrap();
+        let month: Period = "1mo".parse().unwrap();
+        assert_eq!(
+            month.before(moment("2021-03-31T12:00:00Z")),
+            moment("2021-02-28T12:00:00Z")
+        );
+        assert_eq!(
+            month.before(moment("2020-03-31T12:00:00Z")),
+            moment("2020-02-29T12:00:00Z")
+        );
+        let year: Period = "1y".parse().unwrap();
+        assert_eq!(
+            year.before(moment("2021-02-28T00:00:00Z")),
+            moment("2020-02-28T00:00:00Z")
+        );
+        assert_eq!(
+            "1y1mo1d1h"
+                .parse::<Period>()
+                .unwrap()
+                .before(moment("2021-01-01T00:00:00Z")),
+            moment("2019-11-29T23:00:00Z")
+        );
+        let huge = Period {
+            years: Some(u32::MAX),
+            ..Default::default()
+        };
+        assert_eq!(huge.to_timestamp(), chrono::MIN_DATETIME);
+    }
+
+    #[test]
+    fn journal_dates_are_local_and_checked() {
+        let now = chrono::FixedOffset::east(2 * 3600)
+            .ymd(2020, 10, 14)
+            .and_hms(10, 0, 0);
+        let query = JournalQuery {
+            after: Some("2020-10-01".to_string()),
+            before: Some("2020-10-05 08:30".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            query.after_to_timestamp(&now).unwrap(),
+            Some(parse_timestamp("2020-09-30T22:00:00Z", false).unwrap())
+        );
+        assert_eq!(
+            query.before_to_timestamp(&now).unwrap(),
+            Some(parse_timestamp("2020-10-05T06:30:00Z", false).unwrap())
+        );
+        let query = JournalQuery {
+            after: Some("2020-13-01".to_string()),
+            ..Default::default()
+        };
+        assert!(query.after_to_timestamp(&now).is_err());
+        assert_eq!(query.before_to_timestamp(&now), Ok(None));
+
+        let query = JournalQuery {
+            during: Some(During::Range("yesterday".to_string())),
+            ..Default::default()
+        };
+        let (before, after) = query.bounds(&now).unwrap();
+        assert_eq!(before, parse_timestamp("2020-10-13T22:00:00Z", false));
+        assert_eq!(
+            after,
+            parse_timestamp("2020-10-12T21:59:59.999999999Z", false)
+        );
+        let query = JournalQuery {
+            after: Some("last week".to_string()),
+            ..Default::default()
+        };
+        let (_, after) = query.bounds(&now).unwrap();
+        assert_eq!(after, parse_timestamp("2020-10-04T22:00:00Z", false));
     }
 
-    pub fn to_timestamp(&self) -> Timestamp {
-        let delta = self.to_timedelta();
-        chrono::Utc::now().checked_sub_signed(delta).unwrap()
+    #[test]
+    fn dates_and_named_ranges_start_alike() {
+        let now = chrono::FixedOffset::east(2 * 3600)
+            .ymd(2020, 10, 14)
+            .and_hms(10, 0, 0);
+        let after = |s: &str| {
+            JournalQuery {
+                after: Some(s.to_string()),
+                ..Default::default()
+            }
+            .after_to_timestamp(&now)
+            .unwrap()
+        };
+        assert_eq!(after("2020-10-13"), after("yesterday"));
+        assert_eq!(after("2020-W42"), after("this week"));
+        assert_eq!(
+            after("yesterday"),
+            parse_timestamp("2020-10-12T22:00:00Z", false)
+        );
     }
 }
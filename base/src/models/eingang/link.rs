@@ -0,0 +1,111 @@
+//! Wiki-style links between elements.
+//!
+//! A link is written as `[[...]]` in the content of a note or task. If the
+//! text between the brackets is a UUID it references the element with this
+//! UUID, otherwise the element with this title (ignoring case).
+use serde::{Deserialize, Serialize};
+
+/// Target of a single link
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkTarget {
+    Uuid(uuid::Uuid),
+    Title(String),
+}
+
+impl LinkTarget {
+    pub fn parse(text: &str) -> Self {
+        let text = text.trim();
+        match text.parse() {
+            Ok(uuid) => LinkTarget::Uuid(uuid),
+            Err(_) => LinkTarget::Title(text.to_string()),
+        }
+    }
+    /// Target in a canonical form, i.e. titles are compared case insensitive
+    pub fn normalized(&self) -> Self {
+        match self {
+            LinkTarget::Uuid(u) => LinkTarget::Uuid(*u),
+            LinkTarget::Title(t) => LinkTarget::Title(normalize_title(t)),
+        }
+    }
+}
+
+impl std::fmt::Display for LinkTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkTarget::Uuid(u) => write!(f, "[[{}]]", u),
+            LinkTarget::Title(t) => write!(f, "[[{}]]", t),
+        }
+    }
+}
+
+pub fn normalize_title(title: &str) -> String {
+    title.trim().to_lowercase()
+}
+
+/// Return all links in `content` in order of their first appearance
+pub fn parse_links(content: &str) -> Vec<LinkTarget> {
+    let mut links = Vec::new();
+    let mut rest = content;
+    while let Some(start) = rest.find("[[") {
+        rest = &rest[start + 2..];
+        let end = match rest.find("]]") {
+            Some(end) => end,
+            None => break,
+        };
+        let inner = &rest[..end];
+        if inner.contains(&['[', ']', '\n'][..]) {
+            // Skip this opening bracket, another link might start inside
+            continue;
+        }
+        rest = &rest[end + 2..];
+        if inner.trim().is_empty() {
+            continue;
+        }
+        let link = LinkTarget::parse(inner);
+        if !links.contains(&link) {
+            links.push(link);
+        }
+    }
+    links
+}
+
+/// An element which is the source or target of a link
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LinkedElement {
+    pub uuid: uuid::Uuid,
+    /// Content type of the element, e.g. `Note`
+    pub kind: String,
+    pub title: String,
+}
+
+/// All outgoing links of an element
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct LinksResponse {
+    pub links: Vec<LinkedElement>,
+    /// Links which do not match any element
+    pub unresolved: Vec<LinkTarget>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_links, LinkTarget};
+
+    #[test]
+    fn parse_uuid_and_title_links() {
+        let uuid = uuid::Uuid::new_v4();
+        let content = format!(
+            "See [[{}]] and [[ Some Title ]], [[Some Title]] again. [[]] [not] [[open [[inner]]",
+            uuid
+        );
+        let links = parse_links(&content);
+        assert_eq!(
+            links,
+            vec![
+                LinkTarget::Uuid(uuid),
+                LinkTarget::Title("Some Title".to_string()),
+                LinkTarget::Title("inner".to_string()),
+            ]
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use super::Timestamp;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub(crate) struct Meta {
     created_on: Timestamp,
     last_modified: Timestamp,
@@ -22,11 +22,17 @@ impl Meta {
     pub fn update_modified_date(&mut self) {
         self.last_modified = chrono::Utc::now()
     }
+    pub fn created_on(&self) -> Timestamp {
+        self.created_on
+    }
+    pub fn last_modified(&self) -> Timestamp {
+        self.last_modified
+    }
 }
 
 impl PartialOrd<Timestamp> for Meta {
     fn partial_cmp(&self, other: &Timestamp) -> Option<std::cmp::Ordering> {
-        self.last_modified.partial_cmp(&other)
+        self.last_modified.partial_cmp(other)
     }
 }
 
@@ -4,8 +4,92 @@ pub trait Idable {
 
 pub type Timestamp = chrono::DateTime<chrono::Utc>;
 
+/// Formats of dates with time but without offset
+const DATE_TIMES: [&str; 4] = [
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Parse a moment given as RFC 3339 timestamp or as date `YYYY-MM-DD` in UTC.
+/// Dates are interpreted as the start or, with `end_of_day`, the end of the
+/// day.
+pub fn parse_timestamp(s: &str, end_of_day: bool) -> Option<Timestamp> {
+    parse_moment(s, end_of_day, &chrono::Utc)
+}
+
+/// Parse a moment given as RFC 3339 timestamp, as date and time
+/// `YYYY-MM-DD HH:MM[:SS]` (also with `T` as separator) or as date
+/// `YYYY-MM-DD`. Moments without offset are local to the time zone `tz`, dates
+/// are interpreted as the start or, with `end_of_day`, the end of the day.
+pub fn parse_moment<Tz: chrono::TimeZone>(s: &str, end_of_day: bool, tz: &Tz) -> Option<Timestamp> {
+    let s = s.trim();
+    if let Ok(t) = chrono::DateTime::parse_from_rfc3339(s) {
+        return Some(t.with_timezone(&chrono::Utc));
+    }
+    if let Some(t) = DATE_TIMES
+        .iter()
+        .find_map(|f| chrono::NaiveDateTime::parse_from_str(s, f).ok())
+    {
+        return from_local(tz, t);
+    }
+    let date = chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()?;
+    let time = if end_of_day {
+        date.and_hms(23, 59, 59)
+    } else {
+        date.and_hms(0, 0, 0)
+    };
+    from_local(tz, time)
+}
+
+/// Convert a local time of the time zone to UTC. Times which occur twice when
+/// the clocks are turned back resolve to the earlier moment, times skipped
+/// when the clocks are turned forward to the moment one hour later.
+pub fn from_local<Tz: chrono::TimeZone>(tz: &Tz, time: chrono::NaiveDateTime) -> Option<Timestamp> {
+    tz.from_local_datetime(&time)
+        .earliest()
+        .or_else(|| {
+            tz.from_local_datetime(&(time + chrono::Duration::hours(1)))
+                .earliest()
+        })
+        .map(|t| t.with_timezone(&chrono::Utc))
+}
+
+/// Move the date by `months` (backwards if negative) and put it on `day` of
+/// the resulting month, or on its last day if the month is shorter
+pub(crate) fn add_months(
+    date: chrono::NaiveDate,
+    months: i64,
+    day: u32,
+) -> Option<chrono::NaiveDate> {
+    use chrono::Datelike;
+    use std::convert::TryFrom;
+    let total = date.year() as i64 * 12 + date.month0() as i64 + months;
+    let year = i32::try_from(total.div_euclid(12)).ok()?;
+    let month = total.rem_euclid(12) as u32 + 1;
+    (1..=day.max(1))
+        .rev()
+        .find_map(|day| chrono::NaiveDate::from_ymd_opt(year, month, day))
+}
+
+pub mod agenda;
+pub mod dependency;
+pub mod error;
+pub mod expanded;
+pub mod fsck;
 pub mod journal;
+pub mod link;
 pub mod meta;
 pub mod note;
+pub mod quarantine;
+pub mod query;
+pub mod range;
+pub mod recurrence;
+pub mod revision;
+pub mod search;
+pub mod tag;
 pub mod task;
 pub mod thread;
+pub mod tree;
+pub mod urgency;
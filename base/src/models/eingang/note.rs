@@ -1,3 +1,4 @@
+use super::tag::{Tagged, Tags};
 use super::{meta::Meta, Idable};
 use serde::{Deserialize, Serialize};
 
@@ -5,13 +6,19 @@ use serde::{Deserialize, Serialize};
 pub struct NoteQuery {
     pub title: Option<String>,
     pub content: Option<String>,
+    /// Explicit tags of the element, replacing the existing ones on update
+    pub tags: Option<Vec<String>>,
+    /// Only return elements with this tag or one of its descendants
+    pub tag: Option<String>,
 }
 
 /// The Note struct.
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Note {
     pub title: String,
     pub content: String,
+    #[serde(default)]
+    pub tags: Tags,
     pub(crate) meta: Meta,
 }
 
@@ -34,6 +41,12 @@ impl Note {
     pub fn update_modified_date(&mut self) {
         self.meta.update_modified_date()
     }
+    pub fn created_on(&self) -> Timestamp {
+        self.meta.created_on()
+    }
+    pub fn last_modified(&self) -> Timestamp {
+        self.meta.last_modified()
+    }
 }
 
 impl Idable for Note {
@@ -42,6 +55,29 @@ impl Idable for Note {
     }
 }
 
+impl Tagged for Note {
+    fn explicit_tags(&self) -> &Tags {
+        &self.tags
+    }
+    fn tagged_content(&self) -> &str {
+        &self.content
+    }
+}
+
+use super::Timestamp;
+
+impl PartialOrd<Timestamp> for Note {
+    fn partial_cmp(&self, other: &Timestamp) -> Option<std::cmp::Ordering> {
+        self.meta.partial_cmp(other)
+    }
+}
+
+impl PartialEq<Timestamp> for Note {
+    fn eq(&self, other: &Timestamp) -> bool {
+        self.meta == *other
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{Idable, Note};
@@ -65,21 +101,7 @@ mod tests {
         let mut thread = Thread::new();
         thread.add_note(note.get_uuid());
         thread.add_task(task.get_uuid());
-        assert_eq!(thread.tasks[0], task.get_uuid());
-        assert_eq!(thread.notes[0], note.get_uuid());
-    }
-}
-
-use super::Timestamp;
-
-impl PartialOrd<Timestamp> for Note {
-    fn partial_cmp(&self, other: &Timestamp) -> Option<std::cmp::Ordering> {
-        self.meta.partial_cmp(&other)
-    }
-}
-
-impl PartialEq<Timestamp> for Note {
-    fn eq(&self, other: &Timestamp) -> bool {
-        self.meta == *other
+        assert_eq!(thread.tasks()[0], task.get_uuid());
+        assert_eq!(thread.notes()[0], note.get_uuid());
     }
 }
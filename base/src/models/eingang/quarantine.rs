@@ -0,0 +1,25 @@
+use super::Timestamp;
+use serde::{Deserialize, Serialize};
+
+/// An element which could not be read and was moved out of the storage
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Quarantined {
+    /// Content type of the element, e.g. `Note`
+    pub kind: String,
+    /// File name or UUID of the element in quarantine
+    pub name: String,
+    /// Reason why the element could not be read
+    pub error: String,
+    pub quarantined_on: Timestamp,
+}
+
+impl Quarantined {
+    pub fn new(kind: &str, name: &str, error: String) -> Self {
+        Quarantined {
+            kind: kind.to_string(),
+            name: name.to_string(),
+            error,
+            quarantined_on: chrono::Utc::now(),
+        }
+    }
+}
@@ -0,0 +1,477 @@
+//! Query language for finding notes, tasks and threads.
+//!
+//! A query is a list of terms separated by whitespace, which all have to
+//! match. A leading `-` negates a term. Terms are either filters of the form
+//! `key:value` or text, which has to occur in the title or content (ignoring
+//! case). Text and values containing whitespace are written in double quotes,
+//! within which `\"` stands for a quote and `\\` for a backslash.
+//!
+//! ```text
+//! type:task status:open after:2020-10-01 invoice -draft "weekly review"
+//! ```
+//!
+//! | filter          | matches                                               |
+//! |-----------------|-------------------------------------------------------|
+//! | `type:task`     | elements of the type (`note`, `task` or `thread`)     |
+//! | `status:open`   | tasks with the status, see [`TaskStatus::from`]       |
+//! | `priority:high` | tasks with the priority, see [`Priority::from`]       |
+//! | `tag:work`      | notes and tasks with the tag or one of its descendants|
+//! | `after:date`    | elements last modified after the start of the day     |
+//! | `before:date`   | elements last modified before the start of the day    |
+//! | `during:2w`     | elements last modified during the last period         |
+//! | `untouched:2w`  | elements last modified before the last period         |
+//!
+//! Dates are given as `YYYY-MM-DD`, `YYYY-MM-DD HH:MM[:SS]` or RFC 3339
+//! timestamps, those without offset in the time zone passed to
+//! [`Query::parse_in`] (UTC when parsed with `str::parse`). Periods are given
+//! in the short form of [`Period`], e.g. `1y6mo`. Periods are relative to the moment
+//! the query is matched, so saved queries stay up to date. Words with a colon
+//! whose prefix is not a filter (e.g. `10:30`) are treated as text.
+use super::journal::Period;
+use super::note::Note;
+use super::tag::Tagged;
+use super::task::{Priority, Task, TaskStatus};
+use super::thread::Thread;
+use super::{parse_moment, Timestamp};
+use chrono::TimeZone;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Display, Formatter};
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct QueryRequest {
+    /// Query string, e.g. `type:task status:open invoice`
+    pub q: Option<String>,
+}
+
+/// Parsed query, matching elements which fulfil all of its terms
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Query {
+    pub terms: Vec<Term>,
+}
+
+/// A single, possibly negated condition of a query
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    pub negated: bool,
+    pub condition: Condition,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Kind(ElementKind),
+    Status(TaskStatus),
+    Priority(Priority),
+    Tag(String),
+    After(Timestamp),
+    Before(Timestamp),
+    /// Last modified during the period up to now
+    During(Period),
+    /// Last modified before the period up to now
+    Untouched(Period),
+    /// Text occurring in the title or content, lower-cased
+    Text(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ElementKind {
+    Note,
+    Task,
+    Thread,
+}
+
+/// Element which can be matched by a query
+pub trait Queryable {
+    fn kind(&self) -> ElementKind;
+    fn last_modified(&self) -> Timestamp;
+    /// Whether the title or content contains the lower-cased `text`
+    fn contains(&self, text: &str) -> bool;
+    fn status(&self) -> Option<TaskStatus> {
+        None
+    }
+    fn priority(&self) -> Option<Priority> {
+        None
+    }
+    fn has_tag(&self, _tag: &str) -> bool {
+        false
+    }
+}
+
+impl Query {
+    pub fn matches<T: Queryable>(&self, element: &T) -> bool {
+        self.terms.iter().all(|t| t.matches(element))
+    }
+    /// Content types which can match the query at all
+    pub fn kinds(&self) -> Vec<ElementKind> {
+        let all = [ElementKind::Note, ElementKind::Task, ElementKind::Thread];
+        all.iter()
+            .copied()
+            .filter(|kind| {
+                self.terms.iter().all(|t| match (&t.condition, t.negated) {
+                    (Condition::Kind(k), negated) => (k == kind) != negated,
+                    (Condition::Status(_), false) | (Condition::Priority(_), false) => {
+                        *kind == ElementKind::Task
+                    }
+                    (Condition::Tag(_), false) => *kind != ElementKind::Thread,
+                    _ => true,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Term {
+    pub fn matches<T: Queryable>(&self, element: &T) -> bool {
+        let matches = match &self.condition {
+            Condition::Kind(kind) => element.kind() == *kind,
+            Condition::Status(status) => element.status() == Some(*status),
+            Condition::Priority(priority) => element.priority() == Some(*priority),
+            Condition::Tag(tag) => element.has_tag(tag),
+            Condition::After(moment) => element.last_modified() > *moment,
+            Condition::Before(moment) => element.last_modified() < *moment,
+            Condition::During(period) => element.last_modified() > period.to_timestamp(),
+            Condition::Untouched(period) => element.last_modified() < period.to_timestamp(),
+            Condition::Text(text) => element.contains(text),
+        };
+        matches != self.negated
+    }
+}
+
+impl Queryable for Note {
+    fn kind(&self) -> ElementKind {
+        ElementKind::Note
+    }
+    fn last_modified(&self) -> Timestamp {
+        Note::last_modified(self)
+    }
+    fn contains(&self, text: &str) -> bool {
+        contains(&self.title, text) || contains(&self.content, text)
+    }
+    fn has_tag(&self, tag: &str) -> bool {
+        Tagged::has_tag(self, tag)
+    }
+}
+
+impl Queryable for Task {
+    fn kind(&self) -> ElementKind {
+        ElementKind::Task
+    }
+    fn last_modified(&self) -> Timestamp {
+        Task::last_modified(self)
+    }
+    fn contains(&self, text: &str) -> bool {
+        contains(&self.title, text) || contains(&self.content, text)
+    }
+    fn status(&self) -> Option<TaskStatus> {
+        Some(self.status)
+    }
+    fn priority(&self) -> Option<Priority> {
+        Some(self.priority)
+    }
+    fn has_tag(&self, tag: &str) -> bool {
+        Tagged::has_tag(self, tag)
+    }
+}
+
+impl Queryable for Thread {
+    fn kind(&self) -> ElementKind {
+        ElementKind::Thread
+    }
+    fn last_modified(&self) -> Timestamp {
+        Thread::last_modified(self)
+    }
+    fn contains(&self, text: &str) -> bool {
+        contains(&self.title, text) || contains(&self.description, text)
+    }
+}
+
+fn contains(haystack: &str, text: &str) -> bool {
+    haystack.to_lowercase().contains(text)
+}
+
+/// Parse the query with dates in UTC, see [`Query::parse_in`]
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Query::parse_in(s, &chrono::Utc)
+    }
+}
+
+impl Query {
+    /// Parse the query, where dates without offset are local to the time
+    /// zone `tz`
+    pub fn parse_in<Tz: TimeZone>(s: &str, tz: &Tz) -> Result<Query, String> {
+        let mut terms = Vec::new();
+        let mut chars = s.chars().peekable();
+        loop {
+            while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                chars.next();
+            }
+            let negated = match chars.peek() {
+                None => break,
+                Some('-') => {
+                    chars.next();
+                    true
+                }
+                Some(_) => false,
+            };
+            if chars.peek() == Some(&'"') {
+                let text = read_quoted(&mut chars)?;
+                if !text.trim().is_empty() {
+                    let condition = Condition::Text(text.to_lowercase());
+                    terms.push(Term { negated, condition });
+                }
+                continue;
+            }
+            let word = read_word(&mut chars);
+            let condition = match word.split_once(':') {
+                Some((key, value)) if is_filter(key) => {
+                    let value = if value.is_empty() && chars.peek() == Some(&'"') {
+                        read_quoted(&mut chars)?
+                    } else {
+                        value.to_string()
+                    };
+                    parse_filter(key, &value, tz)?
+                }
+                _ if word.is_empty() => continue,
+                _ => Condition::Text(word.to_lowercase()),
+            };
+            terms.push(Term { negated, condition });
+        }
+        Ok(Query { terms })
+    }
+}
+
+const FILTERS: [&str; 8] = [
+    "type",
+    "status",
+    "priority",
+    "tag",
+    "after",
+    "before",
+    "during",
+    "untouched",
+];
+
+fn is_filter(key: &str) -> bool {
+    FILTERS.contains(&key.to_lowercase().as_str())
+}
+
+fn parse_filter<Tz: TimeZone>(key: &str, value: &str, tz: &Tz) -> Result<Condition, String> {
+    let invalid = || format!("Invalid value '{}' for filter '{}'", value, key);
+    let condition = match key.to_lowercase().as_str() {
+        "type" => match value.to_lowercase().as_str() {
+            "note" | "notes" => Condition::Kind(ElementKind::Note),
+            "task" | "tasks" => Condition::Kind(ElementKind::Task),
+            "thread" | "threads" => Condition::Kind(ElementKind::Thread),
+            _ => return Err(invalid()),
+        },
+        "status" => Condition::Status(TaskStatus::from(value.to_string()).ok_or_else(invalid)?),
+        "priority" if !value.is_empty() => {
+            Condition::Priority(Priority::from(value).ok_or_else(invalid)?)
+        }
+        "tag" if !value.is_empty() => Condition::Tag(value.to_string()),
+        "after" => Condition::After(parse_moment(value, false, tz).ok_or_else(invalid)?),
+        "before" => Condition::Before(parse_moment(value, false, tz).ok_or_else(invalid)?),
+        "during" => Condition::During(value.parse().map_err(|_| invalid())?),
+        "untouched" => Condition::Untouched(value.parse().map_err(|_| invalid())?),
+        _ => return Err(invalid()),
+    };
+    Ok(condition)
+}
+
+/// Read up to the next whitespace or quote
+fn read_word(chars: &mut Peekable<Chars>) -> String {
+    let mut word = String::new();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() || c == '"' {
+            break;
+        }
+        word.push(c);
+        chars.next();
+    }
+    word
+}
+
+/// Read a text in double quotes, starting at the opening quote. A backslash
+/// escapes a following quote or backslash.
+fn read_quoted(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    chars.next();
+    let mut text = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Ok(text),
+            '\\' if matches!(chars.peek(), Some('"') | Some('\\')) => {
+                text.extend(chars.next());
+            }
+            c => text.push(c),
+        }
+    }
+    Err(format!("Missing closing quote after '{}'", text))
+}
+
+impl Display for Query {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let terms: Vec<String> = self.terms.iter().map(|t| t.to_string()).collect();
+        write!(f, "{}", terms.join(" "))
+    }
+}
+
+impl Display for Term {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.negated {
+            write!(f, "-")?;
+        }
+        let quoted = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let quote = |s: &str| {
+            if s.contains(char::is_whitespace) || s.contains('"') || s.is_empty() {
+                quoted(s)
+            } else {
+                s.to_string()
+            }
+        };
+        match &self.condition {
+            Condition::Kind(kind) => {
+                let kind = match kind {
+                    ElementKind::Note => "note",
+                    ElementKind::Task => "task",
+                    ElementKind::Thread => "thread",
+                };
+                write!(f, "type:{}", kind)
+            }
+            Condition::Status(status) => write!(f, "status:{}", status),
+            Condition::Priority(priority) => {
+                let priority = match priority {
+                    Priority::High => "high",
+                    Priority::Medium => "medium",
+                    Priority::Low => "low",
+                    Priority::None => "none",
+                };
+                write!(f, "priority:{}", priority)
+            }
+            Condition::Tag(tag) => write!(f, "tag:{}", quote(tag)),
+            Condition::After(moment) => write!(f, "after:{}", moment.to_rfc3339()),
+            Condition::Before(moment) => write!(f, "before:{}", moment.to_rfc3339()),
+            Condition::During(period) => write!(f, "during:{}", period),
+            Condition::Untouched(period) => write!(f, "untouched:{}", period),
+            Condition::Text(text) => {
+                let is_word = text.split_once(':').is_none_or(|(key, _)| !is_filter(key));
+                if is_word && !text.starts_with('-') {
+                    write!(f, "{}", quote(text))
+                } else {
+                    write!(f, "{}", quoted(text))
+                }
+            }
+        }
+    }
+}
+
+impl Serialize for Query {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Query {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Condition, ElementKind, Query, Term};
+    use crate::models::eingang::note::Note;
+    use crate::models::eingang::parse_timestamp;
+    use crate::models::eingang::task::{Task, TaskStatus};
+
+    #[test]
+    fn parse_and_print_queries() {
+        let query: Query =
+            "type:task status:open after:2020-10-01 invoice -draft \"weekly review\""
+                .parse()
+                .unwrap();
+        assert_eq!(query.terms.len(), 6);
+        assert_eq!(query.terms[0].condition, Condition::Kind(ElementKind::Task));
+        assert_eq!(
+            query.terms[4],
+            Term {
+                negated: true,
+                condition: Condition::Text("draft".to_string()),
+            }
+        );
+        assert_eq!(query.to_string().parse::<Query>().unwrap(), query);
+        assert_eq!(query.kinds(), vec![ElementKind::Task]);
+
+        assert!("status:nonsense".parse::<Query>().is_err());
+        assert!("\"unclosed".parse::<Query>().is_err());
+        let query: Query = "at 10:30 tag:\"a b\"".parse().unwrap();
+        assert_eq!(
+            query.terms[1].condition,
+            Condition::Text("10:30".to_string())
+        );
+        assert_eq!(query.terms[2].condition, Condition::Tag("a b".to_string()));
+    }
+
+    #[test]
+    fn quotes_survive_printing_and_parsing() {
+        let query: Query = r#"-"say \"hi\"" tag:"a\"b" "c:\\dir x" c:\dir"#.parse().unwrap();
+        let texts: Vec<_> = query.terms.iter().map(|t| t.condition.clone()).collect();
+        assert_eq!(
+            texts,
+            vec![
+                Condition::Text("say \"hi\"".to_string()),
+                Condition::Tag("a\"b".to_string()),
+                Condition::Text("c:\\dir x".to_string()),
+                Condition::Text("c:\\dir".to_string()),
+            ]
+        );
+        assert_eq!(query.to_string().parse::<Query>().unwrap(), query);
+        assert!(r#""unclosed \""#.parse::<Query>().is_err());
+    }
+
+    #[test]
+    fn dates_are_local_to_the_time_zone() {
+        let tz = chrono::FixedOffset::east(2 * 3600);
+        let query = Query::parse_in("before:2020-10-01 after:\"2020-10-05 08:30\"", &tz).unwrap();
+        assert_eq!(
+            query.terms[0].condition,
+            Condition::Before(parse_timestamp("2020-09-30T22:00:00Z", false).unwrap())
+        );
+        assert_eq!(
+            Query::parse_in("after:2020-10-01", &tz).unwrap().terms[0].condition,
+            Condition::After(parse_timestamp("2020-09-30T22:00:00Z", false).unwrap())
+        );
+        assert_eq!(
+            query.terms[1].condition,
+            Condition::After(parse_timestamp("2020-10-05T06:30:00Z", false).unwrap())
+        );
+        assert_eq!(Query::parse_in(&query.to_string(), &chrono::Utc), Ok(query));
+    }
+
+    #[test]
+    fn match_elements() {
+        let query: Query = "status:open Invoice -draft".parse().unwrap();
+        let mut task = Task::new("Send the invoice".to_string());
+        assert!(query.matches(&task));
+        task.title = "Draft".to_string();
+        assert!(!query.matches(&task));
+        task.title = String::new();
+        task.status = TaskStatus::Closed;
+        assert!(!query.matches(&task));
+        assert!(!query.matches(&Note::new("invoice".to_string())));
+
+        let query: Query = "-status:open invoice".parse().unwrap();
+        assert!(query.matches(&Note::new("invoice".to_string())));
+        assert_eq!(query.kinds().len(), 3);
+
+        let query: Query = "untouched:2w".parse().unwrap();
+        assert!(!query.matches(&task));
+        assert_eq!(query.to_string(), "untouched:2w");
+        assert!("during:soon".parse::<Query>().is_err());
+    }
+}
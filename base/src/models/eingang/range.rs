@@ -0,0 +1,213 @@
+//! Ranges of time given in words, e.g. `last month` or `since monday`.
+//!
+//! Expressions are resolved relative to a moment `now`, whose time zone
+//! defines the days. Weeks start on Monday.
+//!
+//! | expression                          | range                               |
+//! |-------------------------------------|-------------------------------------|
+//! | `today`, `yesterday`, `tomorrow`    | the whole day                       |
+//! | `monday` … `sunday`                 | the last such day, maybe today      |
+//! | `this`/`last`/`next` `week`         | the whole week, month or year       |
+//! | `2020-W42`, `2020-10`, `2020`       | the ISO week, month or year         |
+//! | `2020-10-01`                        | the whole day                       |
+//! | `3 days ago`, `a week ago`          | from the moment until now           |
+//! | `since monday`, `since last month`  | from the start of the range to now  |
+//!
+//! Units of `ago` are `minute`, `hour`, `day`, `week`, `month` and `year`,
+//! singular or plural. RFC 3339 timestamps and dates with time are moments,
+//! which range until now as well.
+use super::journal::Period;
+use super::{add_months, from_local, parse_moment, Timestamp};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Weekday};
+
+/// Range of time from `start` up to, but excluding, `end`. Ranges without
+/// `end` last until now.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Range {
+    pub start: Timestamp,
+    pub end: Option<Timestamp>,
+}
+
+impl Range {
+    /// Parse the expression relative to `now`
+    pub fn parse<Tz: TimeZone>(s: &str, now: &DateTime<Tz>) -> Result<Range, String> {
+        let words: Vec<String> = s.split_whitespace().map(str::to_lowercase).collect();
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        let invalid = || format!("Invalid range '{}'", s.trim());
+        let range = match words.as_slice() {
+            ["since", rest @ ..] if !rest.is_empty() => {
+                let start = Range::parse(&rest.join(" "), now)?.start;
+                Some(Range { start, end: None })
+            }
+            [amount, unit, "ago"] => ago(amount, unit, now),
+            [word] => single(word, now),
+            [which, unit] => calendar(which, unit, now),
+            _ => None,
+        };
+        range.or_else(|| moment(s, now)).ok_or_else(invalid)
+    }
+
+    /// Whole days from the first to the last date in the time zone `tz`
+    fn days<Tz: TimeZone>(first: NaiveDate, last: NaiveDate, tz: &Tz) -> Option<Range> {
+        let start = from_local(tz, first.and_hms(0, 0, 0))?;
+        let end = from_local(tz, last.succ_opt()?.and_hms(0, 0, 0))?;
+        Some(Range {
+            start,
+            end: Some(end),
+        })
+    }
+}
+
+/// Moment the amount of units before now, e.g. `3 days ago`
+fn ago<Tz: TimeZone>(amount: &str, unit: &str, now: &DateTime<Tz>) -> Option<Range> {
+    let amount = match amount {
+        "a" | "an" | "one" => 1,
+        n => n.parse().ok()?,
+    };
+    let unit = match unit.trim_end_matches('s') {
+        "minute" => "min",
+        "hour" => "h",
+        "day" => "d",
+        "week" => "w",
+        "month" => "mo",
+        "year" => "y",
+        _ => return None,
+    };
+    let period: Period = format!("{}{}", amount, unit).parse().ok()?;
+    Some(Range {
+        start: period.before(now.clone()),
+        end: None,
+    })
+}
+
+/// Single words: relative days, weekdays, dates, ISO weeks, months and years
+fn single<Tz: TimeZone>(word: &str, now: &DateTime<Tz>) -> Option<Range> {
+    let tz = now.timezone();
+    let today = now.naive_local().date();
+    let day = |date: NaiveDate| Range::days(date, date, &tz);
+    match word {
+        "today" => return day(today),
+        "yesterday" => return day(today.pred_opt()?),
+        "tomorrow" => return day(today.succ_opt()?),
+        _ => {}
+    }
+    if let Ok(weekday) = word.parse::<Weekday>() {
+        let back =
+            (7 + today.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+        return day(today - Duration::days(back as i64));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+        return day(date);
+    }
+    if let Some((year, week)) = word.split_once("-w") {
+        let monday =
+            NaiveDate::from_isoywd_opt(year.parse().ok()?, week.parse().ok()?, Weekday::Mon)?;
+        return Range::days(monday, monday + Duration::days(6), &tz);
+    }
+    if let Some((year, month)) = word.split_once('-') {
+        if month.len() <= 2 {
+            let first = NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, 1)?;
+            return Range::days(first, add_months(first, 1, 1)?.pred_opt()?, &tz);
+        }
+    }
+    if word.len() == 4 && word.chars().all(|c| c.is_ascii_digit()) {
+        let first = NaiveDate::from_ymd_opt(word.parse().ok()?, 1, 1)?;
+        return Range::days(first, add_months(first, 12, 1)?.pred_opt()?, &tz);
+    }
+    None
+}
+
+/// Timestamps and dates with time, which last until now like `3 days ago`
+fn moment<Tz: TimeZone>(s: &str, now: &DateTime<Tz>) -> Option<Range> {
+    let start = parse_moment(s, false, &now.timezone())?;
+    Some(Range { start, end: None })
+}
+
+/// Calendar weeks, months and years relative to now, e.g. `last month`
+fn calendar<Tz: TimeZone>(which: &str, unit: &str, now: &DateTime<Tz>) -> Option<Range> {
+    let offset = match which {
+        "last" => -1,
+        "this" => 0,
+        "next" => 1,
+        _ => return None,
+    };
+    let today = now.naive_local().date();
+    let (first, length) = match unit {
+        "week" => {
+            let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+            let first = monday + Duration::weeks(offset);
+            return Range::days(first, first + Duration::days(6), &now.timezone());
+        }
+        "month" => (add_months(today, offset, 1)?, 1),
+        "year" => (
+            NaiveDate::from_ymd_opt(today.year() + offset as i32, 1, 1)?,
+            12,
+        ),
+        _ => return None,
+    };
+    Range::days(
+        first,
+        add_months(first, length, 1)?.pred_opt()?,
+        &now.timezone(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Range;
+    use crate::models::eingang::parse_timestamp;
+    use chrono::TimeZone;
+
+    fn range(start: &str, end: Option<&str>) -> Range {
+        Range {
+            start: parse_timestamp(start, false).unwrap(),
+            end: end.map(|e| parse_timestamp(e, false).unwrap()),
+        }
+    }
+
+    #[test]
+    fn parse_named_ranges() {
+        // Wednesday, 2020-10-14 in UTC+2
+        let tz = chrono::FixedOffset::east(2 * 3600);
+        let now = tz.ymd(2020, 10, 14).and_hms(10, 0, 0);
+        let parse = |s| Range::parse(s, &now).unwrap();
+
+        let today = range("2020-10-13T22:00:00Z", Some("2020-10-14T22:00:00Z"));
+        assert_eq!(parse("today"), today);
+        assert_eq!(parse(" Wednesday "), today);
+        assert_eq!(
+            parse("yesterday"),
+            range("2020-10-12T22:00:00Z", Some("2020-10-13T22:00:00Z"))
+        );
+        let week = range("2020-10-11T22:00:00Z", Some("2020-10-18T22:00:00Z"));
+        assert_eq!(parse("this week"), week);
+        assert_eq!(parse("2020-W42"), week);
+        assert_eq!(
+            parse("last month"),
+            range("2020-08-31T22:00:00Z", Some("2020-09-30T22:00:00Z"))
+        );
+        assert_eq!(
+            parse("next year"),
+            range("2020-12-31T22:00:00Z", Some("2021-12-31T22:00:00Z"))
+        );
+        assert_eq!(parse("2020-02").end, Some(parse("2020-03").start));
+        assert_eq!(
+            parse("2020-10-01 08:00"),
+            range("2020-10-01T06:00:00Z", None)
+        );
+        assert_eq!(parse("3 days ago"), range("2020-10-11T08:00:00Z", None));
+        assert_eq!(parse("since monday"), range("2020-10-11T22:00:00Z", None));
+        assert_eq!(parse("since last week").start, parse("2020-10-05").start);
+
+        for invalid in &[
+            "",
+            "since",
+            "someday",
+            "last decade",
+            "3 fortnights ago",
+            "2020-W54",
+        ] {
+            assert!(Range::parse(invalid, &now).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,278 @@
+//! Recurrence rules of repeating tasks.
+//!
+//! A rule is written in the style of iCalendar's RRULE, e.g.
+//! `FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH` for every second week on Monday and
+//! Thursday or `FREQ=MONTHLY;BYMONTHDAY=31` for the last day of every month.
+//! Supported are the keys `FREQ` (`DAILY`, `WEEKLY`, `MONTHLY`), `INTERVAL`
+//! (up to [`MAX_INTERVAL`]), `BYDAY` (weekly only) and `BYMONTHDAY` (monthly
+//! only).
+use super::{add_months, from_local, Timestamp};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Weekday};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+/// Rule for calculating the next occurrence of a task
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    /// Repeat every `interval` days, weeks or months
+    pub interval: u32,
+    /// Days of the week for weekly rules, defaults to the weekday of the date
+    pub weekdays: Vec<Weekday>,
+    /// Day of the month for monthly rules, defaults to the day of the date.
+    /// Days after the end of a month fall on its last day.
+    pub month_day: Option<u32>,
+}
+
+impl Recurrence {
+    pub fn new(frequency: Frequency) -> Self {
+        Recurrence {
+            frequency,
+            interval: 1,
+            weekdays: Vec::new(),
+            month_day: None,
+        }
+    }
+
+    /// Return the rule with the day of the month of monthly rules fixed to the
+    /// one of `first`, the first occurrence of the series. Without it, a day
+    /// clamped to the end of a short month (January 31st becoming February
+    /// 28th) would be taken over by all following occurrences.
+    pub fn anchored<Tz: TimeZone>(&self, first: DateTime<Tz>) -> Recurrence {
+        let mut rule = self.clone();
+        if rule.frequency == Frequency::Monthly && rule.month_day.is_none() {
+            rule.month_day = Some(first.naive_local().day());
+        }
+        rule
+    }
+
+    /// Return the first occurrence after `last`, keeping the local time of
+    /// the day in the time zone of `last`, or nothing if it is out of the
+    /// range of dates
+    pub fn next<Tz: TimeZone>(&self, last: DateTime<Tz>) -> Option<Timestamp> {
+        let local = last.naive_local();
+        let date = local.date();
+        let next = match self.frequency {
+            Frequency::Daily => date.checked_add_signed(Duration::days(self.interval as i64))?,
+            Frequency::Weekly => self.next_weekly(date)?,
+            Frequency::Monthly => {
+                let day = self.month_day.unwrap_or_else(|| date.day());
+                add_months(date, self.interval as i64, day)?
+            }
+        };
+        from_local(&last.timezone(), next.and_time(local.time()))
+    }
+
+    fn next_weekly(&self, date: NaiveDate) -> Option<NaiveDate> {
+        let weeks = Duration::weeks(self.interval as i64);
+        if self.weekdays.is_empty() {
+            return date.checked_add_signed(weeks);
+        }
+        let weekday = |d: NaiveDate| d.weekday().num_days_from_monday();
+        let first_of = |monday: NaiveDate, from: u32| {
+            (from..7).find_map(|day| {
+                let next = monday.checked_add_signed(Duration::days(day as i64))?;
+                Some(next).filter(|n| self.weekdays.contains(&n.weekday()))
+            })
+        };
+        let monday = date.checked_sub_signed(Duration::days(weekday(date) as i64))?;
+        first_of(monday, weekday(date) + 1)
+            .or_else(|| first_of(monday.checked_add_signed(weeks)?, 0))
+    }
+}
+
+/// Largest `INTERVAL`, which keeps occurrences within the range of dates
+pub const MAX_INTERVAL: u32 = 1000;
+
+const WEEKDAYS: [(&str, Weekday); 7] = [
+    ("MO", Weekday::Mon),
+    ("TU", Weekday::Tue),
+    ("WE", Weekday::Wed),
+    ("TH", Weekday::Thu),
+    ("FR", Weekday::Fri),
+    ("SA", Weekday::Sat),
+    ("SU", Weekday::Sun),
+];
+
+impl FromStr for Recurrence {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let s = s.strip_prefix("RRULE:").unwrap_or(s);
+        let mut frequency = None;
+        let mut rule = Recurrence::new(Frequency::Daily);
+        for part in s.split(';').filter(|p| !p.trim().is_empty()) {
+            let (key, value) = match part.find('=') {
+                Some(i) => (
+                    part[..i].trim().to_uppercase(),
+                    part[i + 1..].trim().to_uppercase(),
+                ),
+                None => return Err(format!("Invalid part '{}' of recurrence rule", part)),
+            };
+            match key.as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        _ => return Err(format!("Unsupported frequency '{}'", value)),
+                    })
+                }
+                "INTERVAL" => {
+                    rule.interval = value
+                        .parse()
+                        .ok()
+                        .filter(|i| (1..=MAX_INTERVAL).contains(i))
+                        .ok_or_else(|| format!("Invalid interval '{}'", value))?
+                }
+                "BYDAY" => {
+                    for day in value.split(',') {
+                        let weekday = WEEKDAYS
+                            .iter()
+                            .find(|(name, _)| *name == day.trim())
+                            .map(|(_, w)| *w)
+                            .ok_or_else(|| format!("Invalid weekday '{}'", day))?;
+                        if !rule.weekdays.contains(&weekday) {
+                            rule.weekdays.push(weekday);
+                        }
+                    }
+                }
+                "BYMONTHDAY" => {
+                    rule.month_day = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|d| (1..=31).contains(d))
+                            .ok_or_else(|| format!("Invalid day of month '{}'", value))?,
+                    )
+                }
+                _ => return Err(format!("Unsupported key '{}' in recurrence rule", key)),
+            }
+        }
+        rule.frequency = frequency.ok_or_else(|| "Recurrence rule needs FREQ".to_string())?;
+        if !rule.weekdays.is_empty() && rule.frequency != Frequency::Weekly {
+            return Err("BYDAY is only supported for weekly rules".to_string());
+        }
+        if rule.month_day.is_some() && rule.frequency != Frequency::Monthly {
+            return Err("BYMONTHDAY is only supported for monthly rules".to_string());
+        }
+        Ok(rule)
+    }
+}
+
+impl Display for Recurrence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+        };
+        write!(f, "FREQ={}", frequency)?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if !self.weekdays.is_empty() {
+            let days: Vec<&str> = WEEKDAYS
+                .iter()
+                .filter(|(_, w)| self.weekdays.contains(w))
+                .map(|(name, _)| *name)
+                .collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+        if let Some(day) = self.month_day {
+            write!(f, ";BYMONTHDAY={}", day)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for Recurrence {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Recurrence {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Recurrence;
+    use crate::models::eingang::parse_timestamp;
+
+    fn next(rule: &str, date: &str) -> String {
+        let rule: Recurrence = rule.parse().unwrap();
+        let last = parse_timestamp(date, false).unwrap();
+        rule.next(last).unwrap().format("%Y-%m-%d").to_string()
+    }
+
+    #[test]
+    fn calculate_next_occurrences() {
+        assert_eq!(next("FREQ=DAILY;INTERVAL=3", "2021-02-27"), "2021-03-02");
+        // 2021-03-03 is a Wednesday
+        assert_eq!(next("FREQ=WEEKLY;BYDAY=MO,TH", "2021-03-03"), "2021-03-04");
+        assert_eq!(next("FREQ=WEEKLY;BYDAY=MO,TH", "2021-03-04"), "2021-03-08");
+        assert_eq!(
+            next("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO", "2021-03-01"),
+            "2021-03-15"
+        );
+        assert_eq!(next("FREQ=WEEKLY", "2021-03-01"), "2021-03-08");
+        assert_eq!(
+            next("FREQ=MONTHLY;BYMONTHDAY=31", "2021-01-31"),
+            "2021-02-28"
+        );
+        assert_eq!(next("FREQ=MONTHLY;INTERVAL=12", "2020-02-29"), "2021-02-28");
+    }
+
+    #[test]
+    fn huge_intervals_end_instead_of_overflowing() {
+        assert!("FREQ=WEEKLY;INTERVAL=4294967295;BYDAY=MO"
+            .parse::<Recurrence>()
+            .is_err());
+        assert_eq!(
+            next("FREQ=WEEKLY;INTERVAL=1000;BYDAY=MO", "2021-03-01"),
+            "2040-04-30"
+        );
+        let mut rule: Recurrence = "FREQ=WEEKLY;BYDAY=MO".parse().unwrap();
+        rule.interval = u32::MAX;
+        let last = parse_timestamp("2021-03-01", false).unwrap();
+        assert_eq!(rule.next(last), None);
+        rule.weekdays.clear();
+        assert_eq!(rule.next(last), None);
+    }
+
+    #[test]
+    fn anchored_monthly_rules_keep_their_day() {
+        let first = parse_timestamp("2021-01-31", false).unwrap();
+        let rule = "FREQ=MONTHLY"
+            .parse::<Recurrence>()
+            .unwrap()
+            .anchored(first);
+        assert_eq!(rule.to_string(), "FREQ=MONTHLY;BYMONTHDAY=31");
+        let february = rule.next(first).unwrap();
+        assert_eq!(february.format("%Y-%m-%d").to_string(), "2021-02-28");
+        let march = rule.next(february).unwrap();
+        assert_eq!(march.format("%Y-%m-%d").to_string(), "2021-03-31");
+    }
+
+    #[test]
+    fn parse_and_print_rules() {
+        let rule: Recurrence = "RRULE:freq=weekly;byday=TH,MO;interval=2".parse().unwrap();
+        assert_eq!(rule.to_string(), "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH");
+        assert!("FREQ=YEARLY".parse::<Recurrence>().is_err());
+        assert!("FREQ=DAILY;BYDAY=MO".parse::<Recurrence>().is_err());
+        assert!("INTERVAL=2".parse::<Recurrence>().is_err());
+    }
+}
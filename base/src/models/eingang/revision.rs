@@ -0,0 +1,57 @@
+use super::Timestamp;
+use serde::{Deserialize, Serialize};
+
+/// Summary of a single revision of an element. Revisions are numbered from 1
+/// (oldest) to the current version.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RevisionInfo {
+    pub revision: usize,
+    pub title: String,
+    pub last_modified: Timestamp,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct DiffQuery {
+    /// Older revision, defaults to the one before `to`
+    pub from: Option<usize>,
+    /// Newer revision, defaults to the current version
+    pub to: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Equal,
+    Insert,
+    Delete,
+}
+
+/// A single line of a line-based diff
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DiffLine {
+    pub change: ChangeKind,
+    pub line: String,
+}
+
+/// Differences of the content between two revisions
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RevisionDiff {
+    pub from: usize,
+    pub to: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+impl std::fmt::Display for RevisionDiff {
+    /// Print the diff in the style of `diff -u` without hunk headers
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            let sign = match line.change {
+                ChangeKind::Equal => ' ',
+                ChangeKind::Insert => '+',
+                ChangeKind::Delete => '-',
+            };
+            writeln!(f, "{}{}", sign, line.line)?;
+        }
+        Ok(())
+    }
+}
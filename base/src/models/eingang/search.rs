@@ -0,0 +1,26 @@
+//! Full-text search over notes and tasks.
+use serde::{Deserialize, Serialize};
+
+/// Number of hits returned if no limit is given
+pub const SEARCH_LIMIT: usize = 20;
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct SearchQuery {
+    /// Words to search for, text in double quotes is matched as phrase
+    pub q: Option<String>,
+    /// Maximal number of hits
+    pub limit: Option<usize>,
+}
+
+/// Note or task matching a search, best hits first
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub uuid: uuid::Uuid,
+    /// Content type of the element, e.g. `Note`
+    pub kind: String,
+    pub title: String,
+    pub score: f64,
+    /// Part of the content around the first match as HTML, with the content
+    /// escaped and all matches wrapped in `<mark>` and `</mark>`
+    pub snippet: String,
+}
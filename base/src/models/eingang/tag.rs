@@ -0,0 +1,135 @@
+//! Hierarchical tags of notes and tasks.
+//!
+//! Tags are lowercase paths separated by `/`, e.g. `work/clienta`. Besides the
+//! explicit `tags` of an element, every `#tag` in its content counts as tag.
+//! A query for a tag also matches all of its descendants, i.e. `work`
+//! matches `work/clienta`.
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+pub type Tags = BTreeSet<String>;
+
+/// Elements which can be tagged
+pub trait Tagged {
+    /// Explicitly set tags
+    fn explicit_tags(&self) -> &Tags;
+    /// Text which is searched for inline `#tags`
+    fn tagged_content(&self) -> &str;
+
+    /// All tags, i.e. the explicit ones and those in the content
+    fn tags(&self) -> Tags {
+        let mut tags = parse_tags(self.tagged_content());
+        tags.extend(self.explicit_tags().iter().cloned());
+        tags
+    }
+    /// Whether the element has the tag `query` or one of its descendants
+    fn has_tag(&self, query: &str) -> bool {
+        match normalize_tag(query) {
+            Some(query) => self.tags().iter().any(|t| tag_matches(t, &query)),
+            None => false,
+        }
+    }
+}
+
+/// Keep only elements with the tag `query` or one of its descendants
+pub fn filter_by_tag<T: Tagged>(elements: Vec<T>, query: &str) -> Vec<T> {
+    elements.into_iter().filter(|e| e.has_tag(query)).collect()
+}
+
+/// Bring a tag in its canonical form, i.e. lowercase without a leading `#`
+/// and without empty path segments
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let tag = tag.trim().trim_start_matches('#').to_lowercase();
+    let segments: Vec<&str> = tag
+        .split('/')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect();
+    if segments.is_empty() {
+        None
+    } else {
+        Some(segments.join("/"))
+    }
+}
+
+/// Normalize all given tags and drop empty ones
+pub fn normalize_tags<I: IntoIterator<Item = S>, S: AsRef<str>>(tags: I) -> Tags {
+    tags.into_iter()
+        .filter_map(|t| normalize_tag(t.as_ref()))
+        .collect()
+}
+
+/// Whether `tag` equals `query` or is one of its descendants
+pub fn tag_matches(tag: &str, query: &str) -> bool {
+    tag == query || (tag.starts_with(query) && tag[query.len()..].starts_with('/'))
+}
+
+/// Return all ancestors of the tag including itself, e.g. `a`, `a/b` for `a/b`
+pub fn tag_ancestors(tag: &str) -> Vec<String> {
+    tag.match_indices('/')
+        .map(|(i, _)| tag[..i].to_string())
+        .chain(std::iter::once(tag.to_string()))
+        .collect()
+}
+
+/// Return all inline `#tags` of the content. A tag starts with a letter after
+/// a whitespace and consists of letters, digits, `_`, `-` and `/`.
+pub fn parse_tags(content: &str) -> Tags {
+    let mut tags = Tags::new();
+    let mut previous = ' ';
+    let mut chars = content.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let starts_tag = c == '#' && previous.is_whitespace();
+        previous = c;
+        if !starts_tag || !chars.peek().is_some_and(|(_, n)| n.is_alphabetic()) {
+            continue;
+        }
+        let start = i + 1;
+        let mut end = start;
+        while let Some(&(j, n)) = chars.peek() {
+            if n.is_alphanumeric() || n == '_' || n == '-' || n == '/' {
+                end = j + n.len_utf8();
+                previous = n;
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        if let Some(tag) = normalize_tag(&content[start..end]) {
+            tags.insert(tag);
+        }
+    }
+    tags
+}
+
+/// Number of elements using a tag or one of its descendants
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_tag, parse_tags, tag_ancestors, tag_matches};
+
+    #[test]
+    fn parse_inline_tags() {
+        let tags = parse_tags("# Heading\nSome #work/ClientA text,#no #1 and #todo.");
+        let tags: Vec<&str> = tags.iter().map(String::as_str).collect();
+        assert_eq!(tags, vec!["todo", "work/clienta"]);
+    }
+
+    #[test]
+    fn hierarchical_matching() {
+        assert_eq!(
+            normalize_tag(" #Work//ClientA/ "),
+            Some("work/clienta".into())
+        );
+        assert!(tag_matches("work/clienta", "work"));
+        assert!(tag_matches("work", "work"));
+        assert!(!tag_matches("workshop", "work"));
+        assert!(!tag_matches("work", "work/clienta"));
+        assert_eq!(tag_ancestors("a/b/c"), vec!["a", "a/b", "a/b/c"]);
+    }
+}
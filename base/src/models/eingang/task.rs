@@ -1,27 +1,92 @@
 use super::note::Note;
+use super::recurrence::Recurrence;
+use super::tag::{Tagged, Tags};
+use super::thread::TaskUuid;
 use super::{meta::Meta, Idable};
+use chrono::{DateTime, TimeZone};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct TaskQuery {
     pub title: Option<String>,
     pub content: Option<String>,
+    /// Explicit tags of the element, replacing the existing ones on update
+    pub tags: Option<Vec<String>>,
+    /// Only return elements with this tag or one of its descendants
+    pub tag: Option<String>,
     pub status: Option<String>,
+    /// Deadline as `YYYY-MM-DD` (end of the day) or RFC 3339, empty to remove
+    pub due: Option<String>,
+    /// Start date as `YYYY-MM-DD` (start of the day) or RFC 3339, empty to remove
+    pub scheduled: Option<String>,
+    /// Recurrence rule like `FREQ=WEEKLY;BYDAY=MO`, empty to remove
+    pub recurrence: Option<String>,
+    /// UUIDs of the tasks blocking this task, replacing the existing ones
+    pub blocked_by: Option<Vec<String>>,
+    pub priority: Option<String>,
+    /// Order of the returned tasks
+    pub sort: Option<TaskSort>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+/// Orders of task lists
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskSort {
+    /// Oldest tasks first
+    Created,
+    /// Tasks with the earliest due date first, tasks without one last
+    Due,
+    /// Most urgent tasks first, each with its `urgency` score
+    Urgency,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Task {
     pub title: String,
     pub content: String,
+    #[serde(default)]
+    pub tags: Tags,
     pub status: TaskStatus,
+    #[serde(default)]
+    pub priority: Priority,
+    /// Moment the task has to be done by
+    #[serde(default)]
+    pub due: Option<Timestamp>,
+    /// Moment the work on the task should start
+    #[serde(default)]
+    pub scheduled: Option<Timestamp>,
+    /// Rule for creating the next instance once the task is closed
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
+    /// UUID of the first task of a recurring series
+    #[serde(default)]
+    pub series: Option<uuid::Uuid>,
+    /// Next instance of the recurring series, once this task created it
+    #[serde(default)]
+    pub successor: Option<TaskUuid>,
+    /// Tasks which have to be done before this one
+    #[serde(default)]
+    pub blocked_by: Vec<TaskUuid>,
+    /// All changes of the status, oldest first
+    #[serde(default)]
+    pub history: Vec<StatusChange>,
     pub(crate) meta: Meta,
 }
 
+/// A single change of the status of a task
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StatusChange {
+    pub on: Timestamp,
+    pub from: TaskStatus,
+    pub to: TaskStatus,
+}
+
 impl From<Note> for Task {
     fn from(note: Note) -> Self {
         Task {
             content: note.content,
             title: note.title,
+            tags: note.tags,
             meta: note.meta,
             ..Default::default()
         }
@@ -50,9 +115,76 @@ impl Task {
             ..Default::default()
         }
     }
+    /// Return the next instance of a recurring task. It is due at the first
+    /// occurrence after the due date of this task, which lies in the future.
+    /// Tasks without a due date use `now` as anchor. The day of the month of
+    /// monthly rules is fixed to the one of the first instance of the series,
+    /// days are those of the time zone of `now`.
+    pub fn next_instance<Tz: TimeZone>(&self, now: DateTime<Tz>) -> Option<Task> {
+        let tz = now.timezone();
+        let now = now.with_timezone(&chrono::Utc);
+        let last = self.due.unwrap_or(now);
+        let rule = self.recurrence.as_ref()?.anchored(last.with_timezone(&tz));
+        let mut due = rule.next(last.with_timezone(&tz))?;
+        while due <= now {
+            due = rule.next(due.with_timezone(&tz))?;
+        }
+        Some(Task {
+            title: self.title.clone(),
+            content: self.content.clone(),
+            tags: self.tags.clone(),
+            due: Some(due),
+            scheduled: self.scheduled.map(|s| s + (due - last)),
+            recurrence: Some(rule),
+            priority: self.priority,
+            series: Some(self.series.unwrap_or_else(|| self.get_uuid())),
+            ..Default::default()
+        })
+    }
+    /// Change the status, if the transition is allowed, and record it in the
+    /// history. Setting the current status again is a no-op.
+    pub fn set_status(&mut self, status: TaskStatus) -> Result<(), String> {
+        if status == self.status {
+            return Ok(());
+        }
+        if !self.status.can_change_to(status) {
+            return Err(format!(
+                "Status of task cannot change from {} to {}",
+                self.status, status
+            ));
+        }
+        self.history.push(StatusChange {
+            on: chrono::Utc::now(),
+            from: self.status,
+            to: status,
+        });
+        self.status = status;
+        Ok(())
+    }
+    /// Moment the task was closed the last time, if it is closed
+    pub fn closed_on(&self) -> Option<Timestamp> {
+        if self.status != TaskStatus::Closed {
+            return None;
+        }
+        self.history
+            .iter()
+            .rev()
+            .find(|c| c.to == TaskStatus::Closed)
+            .map(|c| c.on)
+    }
+    /// Time from the creation of the task until it was closed
+    pub fn cycle_time(&self) -> Option<chrono::Duration> {
+        self.closed_on().map(|c| c - self.created_on())
+    }
     pub fn update_modified_date(&mut self) {
         self.meta.update_modified_date()
     }
+    pub fn created_on(&self) -> Timestamp {
+        self.meta.created_on()
+    }
+    pub fn last_modified(&self) -> Timestamp {
+        self.meta.last_modified()
+    }
 }
 
 impl Idable for Task {
@@ -61,22 +193,60 @@ impl Idable for Task {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum TaskStatus {
+    #[default]
     Open,
     Waiting,
     Closed,
     Deactivated,
 }
 
-impl Default for TaskStatus {
-    fn default() -> Self {
-        TaskStatus::Open
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    High,
+    Medium,
+    Low,
+    #[default]
+    None,
+}
+
+impl Priority {
+    pub fn from(priority: &str) -> Option<Self> {
+        match priority.to_lowercase().as_str() {
+            "high" | "h" => Some(Priority::High),
+            "medium" | "m" => Some(Priority::Medium),
+            "low" | "l" => Some(Priority::Low),
+            "none" | "" => Some(Priority::None),
+            _ => None,
+        }
     }
 }
 
 impl TaskStatus {
+    /// Whether work on a task with this status is still outstanding
+    pub fn is_active(self) -> bool {
+        matches!(self, TaskStatus::Open | TaskStatus::Waiting)
+    }
+    /// Allowed transitions between the statuses:
+    ///
+    /// | from        | to                              |
+    /// |-------------|---------------------------------|
+    /// | open        | waiting, closed, deactivated    |
+    /// | waiting     | open, closed, deactivated       |
+    /// | closed      | open                            |
+    /// | deactivated | open                            |
+    ///
+    /// Closed and deactivated tasks therefore have to be reopened explicitly.
+    pub fn can_change_to(self, to: TaskStatus) -> bool {
+        use TaskStatus::*;
+        match self {
+            Open | Waiting => to != self,
+            Closed | Deactivated => to == Open,
+        }
+    }
     pub fn from(stst: String) -> Option<Self> {
         match stst.to_lowercase().as_str() {
             "closed" | "done" => Some(TaskStatus::Closed),
@@ -88,11 +258,32 @@ impl TaskStatus {
     }
 }
 
+impl std::fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            TaskStatus::Open => "open",
+            TaskStatus::Waiting => "waiting",
+            TaskStatus::Closed => "closed",
+            TaskStatus::Deactivated => "deactivated",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Tagged for Task {
+    fn explicit_tags(&self) -> &Tags {
+        &self.tags
+    }
+    fn tagged_content(&self) -> &str {
+        &self.content
+    }
+}
+
 use super::Timestamp;
 
 impl PartialOrd<Timestamp> for Task {
     fn partial_cmp(&self, other: &Timestamp) -> Option<std::cmp::Ordering> {
-        self.meta.partial_cmp(&other)
+        self.meta.partial_cmp(other)
     }
 }
 
@@ -101,3 +292,84 @@ impl PartialEq<Timestamp> for Task {
         self.meta == *other
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Idable, Priority, Task, TaskStatus};
+    use crate::models::eingang::{parse_moment, parse_timestamp};
+    use chrono::TimeZone;
+
+    #[test]
+    fn next_instance_of_recurring_task() {
+        let now = parse_timestamp("2021-03-10T12:00:00Z", false).unwrap();
+        let mut task = Task::new("report".to_string());
+        task.recurrence = Some("FREQ=WEEKLY;BYDAY=MO".parse().unwrap());
+        task.due = parse_timestamp("2021-03-01", true);
+        task.scheduled = parse_timestamp("2021-02-26", false);
+        task.priority = Priority::High;
+
+        let next = task.next_instance(now).unwrap();
+        assert_eq!(next.due, parse_timestamp("2021-03-15", true));
+        assert_eq!(next.scheduled, parse_timestamp("2021-03-12", false));
+        assert_eq!(next.series, Some(task.get_uuid()));
+        assert_eq!(next.priority, Priority::High);
+        assert_eq!(
+            next.next_instance(now).unwrap().series,
+            Some(task.get_uuid())
+        );
+        assert!(Task::new("once".to_string()).next_instance(now).is_none());
+    }
+
+    #[test]
+    fn monthly_series_keep_the_day_of_the_first_instance() {
+        let now = parse_timestamp("2021-01-01", false).unwrap();
+        let mut task = Task::new("rent".to_string());
+        task.recurrence = Some("FREQ=MONTHLY".parse().unwrap());
+        task.due = parse_timestamp("2021-01-31", true);
+
+        let february = task.next_instance(now).unwrap();
+        let march = february.next_instance(now).unwrap();
+        assert_eq!(february.due, parse_timestamp("2021-02-28", true));
+        assert_eq!(march.due, parse_timestamp("2021-03-31", true));
+        let late = task
+            .next_instance(parse_timestamp("2021-04-15", false).unwrap())
+            .unwrap();
+        assert_eq!(late.due, parse_timestamp("2021-04-30", true));
+    }
+
+    #[test]
+    fn recurring_series_follow_local_days() {
+        let tz = chrono::FixedOffset::west(5 * 3600);
+        let now = tz.ymd(2021, 3, 1).and_hms(12, 0, 0);
+        let mut task = Task::new("review".to_string());
+        task.recurrence = Some("FREQ=WEEKLY;BYDAY=MO".parse().unwrap());
+        // Monday, but already Tuesday in UTC
+        task.due = parse_moment("2021-03-01", true, &tz);
+
+        let next = task.next_instance(now).unwrap();
+        assert_eq!(next.due, parse_moment("2021-03-08", true, &tz));
+    }
+
+    #[test]
+    fn status_transitions_are_recorded() {
+        let mut task = Task::new("task".to_string());
+        task.set_status(TaskStatus::Deactivated).unwrap();
+        assert!(task.set_status(TaskStatus::Closed).is_err());
+        task.set_status(TaskStatus::Open).unwrap();
+        task.set_status(TaskStatus::Closed).unwrap();
+        task.set_status(TaskStatus::Closed).unwrap();
+        assert!(task.set_status(TaskStatus::Waiting).is_err());
+
+        let changes: Vec<_> = task.history.iter().map(|c| (c.from, c.to)).collect();
+        assert_eq!(
+            changes,
+            vec![
+                (TaskStatus::Open, TaskStatus::Deactivated),
+                (TaskStatus::Deactivated, TaskStatus::Open),
+                (TaskStatus::Open, TaskStatus::Closed),
+            ]
+        );
+        assert_eq!(task.closed_on(), Some(task.history[2].on));
+        assert!(task.cycle_time().is_some());
+    }
+}
@@ -1,50 +1,280 @@
+use super::expanded::ExpandedThread;
+use super::query::Query;
+use super::tree::ThreadNode;
 use super::{meta::Meta, Idable, Timestamp};
 use serde::{Deserialize, Serialize};
 
 pub type NoteUuid = uuid::Uuid;
 pub type TaskUuid = uuid::Uuid;
+pub type ThreadUuid = uuid::Uuid;
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(from = "StoredThread")]
 pub struct Thread {
-    pub notes: Vec<NoteUuid>,
-    pub tasks: Vec<TaskUuid>,
+    pub title: String,
+    pub description: String,
+    pub state: ThreadState,
+    /// Notes and tasks in the order of the thread
+    pub items: Vec<ThreadItem>,
+    /// Saved search of the thread. Its items are not stored, but resolved
+    /// from the query whenever the thread is read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<Query>,
+    meta: Meta,
+}
+
+/// Reference to a note, task or sub-thread of a thread
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(tag = "kind", content = "uuid", rename_all = "lowercase")]
+pub enum ThreadItem {
+    Note(NoteUuid),
+    Task(TaskUuid),
+    Thread(ThreadUuid),
+}
+
+impl ThreadItem {
+    pub fn uuid(&self) -> uuid::Uuid {
+        match self {
+            ThreadItem::Note(uuid) | ThreadItem::Task(uuid) | ThreadItem::Thread(uuid) => *uuid,
+        }
+    }
+    /// Name of the content type used in messages
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ThreadItem::Note(_) => "Note",
+            ThreadItem::Task(_) => "Task",
+            ThreadItem::Thread(_) => "Thread",
+        }
+    }
+}
+
+/// Thread as saved, including the separate `notes` and `tasks` lists of
+/// older versions. Their elements are appended to the items, tasks first like
+/// in [`Thread::with_tasks_and_notes`].
+#[derive(Deserialize)]
+struct StoredThread {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    state: ThreadState,
+    #[serde(default)]
+    items: Vec<ThreadItem>,
+    #[serde(default)]
+    notes: Vec<NoteUuid>,
+    #[serde(default)]
+    tasks: Vec<TaskUuid>,
+    #[serde(default)]
+    query: Option<Query>,
     meta: Meta,
 }
 
+impl From<StoredThread> for Thread {
+    fn from(stored: StoredThread) -> Self {
+        let mut items = stored.items;
+        items.extend(tasks_and_notes(stored.tasks, stored.notes));
+        Thread {
+            title: stored.title,
+            description: stored.description,
+            state: stored.state,
+            items,
+            query: stored.query,
+            meta: stored.meta,
+        }
+    }
+}
+
+/// Items of separate lists of tasks and notes, tasks first
+fn tasks_and_notes(tasks: Vec<TaskUuid>, notes: Vec<NoteUuid>) -> Vec<ThreadItem> {
+    let tasks = tasks.into_iter().map(ThreadItem::Task);
+    tasks
+        .chain(notes.into_iter().map(ThreadItem::Note))
+        .collect()
+}
+
+/// Lifecycle of a thread. Archived threads are hidden from listings unless
+/// they are requested explicitly.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ThreadState {
+    #[default]
+    Active,
+    Archived,
+}
+
+/// Turn a title into a URL-safe slug, e.g. `Über Äpfel & Birnen` becomes
+/// `ueber-aepfel-birnen`. Umlauts are transliterated, all other characters
+/// except ASCII letters and digits separate words.
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::with_capacity(title.len());
+    for c in title.chars().flat_map(char::to_lowercase) {
+        let replacement = match c {
+            'a'..='z' | '0'..='9' => {
+                slug.push(c);
+                continue;
+            }
+            'ä' => "ae",
+            'ö' => "oe",
+            'ü' => "ue",
+            'ß' => "ss",
+            _ => "-",
+        };
+        if replacement != "-" {
+            slug.push_str(replacement);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
 impl Thread {
     pub fn new() -> Self {
         Default::default()
     }
-    pub fn with_notes(notes: Vec<NoteUuid>) -> Self {
+    pub fn with_items(items: Vec<ThreadItem>) -> Self {
         Thread {
-            notes,
+            items,
             ..Default::default()
         }
     }
-    pub fn with_tasks(tasks: Vec<TaskUuid>) -> Self {
+    /// Create a saved search, whose items are the elements matching the query
+    pub fn with_query(query: Query) -> Self {
         Thread {
-            tasks,
+            query: Some(query),
             ..Default::default()
         }
     }
+    pub fn with_notes(notes: Vec<NoteUuid>) -> Self {
+        Thread::with_items(notes.into_iter().map(ThreadItem::Note).collect())
+    }
+    pub fn with_tasks(tasks: Vec<TaskUuid>) -> Self {
+        Thread::with_items(tasks.into_iter().map(ThreadItem::Task).collect())
+    }
+    /// Create a thread with the tasks followed by the notes
     pub fn with_tasks_and_notes(tasks: Vec<TaskUuid>, notes: Vec<NoteUuid>) -> Self {
-        Thread {
-            tasks,
-            notes,
-            ..Default::default()
-        }
+        Thread::with_items(tasks_and_notes(tasks, notes))
+    }
+    /// UUIDs of all notes in the order of the thread
+    pub fn notes(&self) -> Vec<NoteUuid> {
+        self.items
+            .iter()
+            .filter_map(|i| match i {
+                ThreadItem::Note(uuid) => Some(*uuid),
+                _ => None,
+            })
+            .collect()
+    }
+    /// UUIDs of all tasks in the order of the thread
+    pub fn tasks(&self) -> Vec<TaskUuid> {
+        self.items
+            .iter()
+            .filter_map(|i| match i {
+                ThreadItem::Task(uuid) => Some(*uuid),
+                _ => None,
+            })
+            .collect()
+    }
+    /// UUIDs of all sub-threads in the order of the thread
+    pub fn threads(&self) -> Vec<ThreadUuid> {
+        self.items
+            .iter()
+            .filter_map(|i| match i {
+                ThreadItem::Thread(uuid) => Some(*uuid),
+                _ => None,
+            })
+            .collect()
     }
     pub fn add_note(&mut self, note: NoteUuid) {
-        self.notes.push(note);
-        self.update_modified_date();
+        self.push(ThreadItem::Note(note));
     }
     pub fn add_task(&mut self, task: TaskUuid) {
-        self.tasks.push(task);
+        self.push(ThreadItem::Task(task));
+    }
+    pub fn add_thread(&mut self, thread: ThreadUuid) {
+        self.push(ThreadItem::Thread(thread));
+    }
+    pub fn push(&mut self, item: ThreadItem) {
+        self.items.push(item);
+        self.update_modified_date();
+    }
+    /// Insert the item before the one at `position`, or at the end if
+    /// `position` equals the number of items
+    pub fn insert(&mut self, position: usize, item: ThreadItem) -> Result<(), String> {
+        if position > self.items.len() {
+            return Err(self.out_of_range(position));
+        }
+        self.items.insert(position, item);
+        self.update_modified_date();
+        Ok(())
+    }
+    /// Move the item at `from` so it ends up at position `to`
+    pub fn move_item(&mut self, from: usize, to: usize) -> Result<(), String> {
+        for &position in &[from, to] {
+            if position >= self.items.len() {
+                return Err(self.out_of_range(position));
+            }
+        }
+        let item = self.items.remove(from);
+        self.items.insert(to, item);
+        self.update_modified_date();
+        Ok(())
+    }
+    /// Remove the first occurrence of the item and return whether it was part
+    /// of the thread
+    pub fn remove(&mut self, item: ThreadItem) -> bool {
+        match self.items.iter().position(|i| *i == item) {
+            Some(p) => {
+                self.items.remove(p);
+                self.update_modified_date();
+                true
+            }
+            None => false,
+        }
+    }
+    /// Replace the order of the items. The new order has to contain exactly
+    /// the items of the thread.
+    pub fn reorder(&mut self, items: Vec<ThreadItem>) -> Result<(), String> {
+        let mut current = self.items.clone();
+        let mut new = items.clone();
+        let key = |i: &ThreadItem| (i.kind(), i.uuid());
+        current.sort_by_key(key);
+        new.sort_by_key(key);
+        if current != new {
+            return Err("New order has to contain exactly the items of the thread".into());
+        }
+        self.items = items;
         self.update_modified_date();
+        Ok(())
+    }
+    fn out_of_range(&self, position: usize) -> String {
+        format!(
+            "Position {} is out of range for thread with {} items",
+            position,
+            self.items.len()
+        )
+    }
+    /// URL-safe slug derived from the title, empty for untitled threads
+    pub fn slug(&self) -> String {
+        slugify(&self.title)
+    }
+    pub fn is_archived(&self) -> bool {
+        self.state == ThreadState::Archived
+    }
+    /// Whether the items of the thread are resolved from a saved search
+    pub fn is_search(&self) -> bool {
+        self.query.is_some()
     }
     pub fn update_modified_date(&mut self) {
         self.meta.update_modified_date()
     }
+    pub fn created_on(&self) -> Timestamp {
+        self.meta.created_on()
+    }
+    pub fn last_modified(&self) -> Timestamp {
+        self.meta.last_modified()
+    }
 }
 
 impl Idable for Thread {
@@ -55,11 +285,41 @@ impl Idable for Thread {
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct ThreadQuery {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub state: Option<ThreadState>,
+    /// Saved search turning the thread into a dynamic one, e.g.
+    /// `type:task status:waiting untouched:2w`, empty to remove
+    pub query: Option<String>,
+    /// Also return archived threads
+    pub archived: Option<bool>,
     pub task: Option<String>,
     pub tasks: Option<Vec<String>>,
     pub note: Option<String>,
     pub notes: Option<Vec<String>>,
+    pub thread: Option<String>,
+    pub threads: Option<Vec<String>>,
+    /// Position to insert an added task or note at, appended if missing
+    pub position: Option<usize>,
     pub filter: Option<ThreadFilter>,
+    /// Return the thread with its sub-threads expanded for `depth` levels
+    pub depth: Option<usize>,
+    /// Include the notes and tasks of all sub-threads in filters and
+    /// expanded threads
+    pub recursive: Option<bool>,
+    /// Return the thread with its notes, tasks and sub-threads resolved
+    pub expand: Option<bool>,
+    /// Order the resolved elements by their last modification
+    pub timeline: Option<bool>,
+}
+
+/// Change of the order of a thread: either the complete new order of all
+/// `items` or a single move of the item at `from` to position `to`
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ReorderQuery {
+    pub items: Option<Vec<ThreadItem>>,
+    pub from: Option<usize>,
+    pub to: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
@@ -73,14 +333,15 @@ pub enum ThreadFilter {
 #[serde(rename_all = "lowercase")]
 pub enum ThreadResponse {
     Threads(Thread),
+    Tree(ThreadNode),
+    Expanded(ExpandedThread),
     Tasks(Vec<TaskUuid>),
     Notes(Vec<NoteUuid>),
 }
 
-
 impl PartialOrd<Timestamp> for Thread {
     fn partial_cmp(&self, other: &Timestamp) -> Option<std::cmp::Ordering> {
-        self.meta.partial_cmp(&other)
+        self.meta.partial_cmp(other)
     }
 }
 
@@ -89,3 +350,53 @@ impl PartialEq<Timestamp> for Thread {
         self.meta == *other
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{slugify, Thread, ThreadItem};
+
+    #[test]
+    fn insert_move_and_remove_items() {
+        let (a, b, c) = (
+            uuid::Uuid::new_v4(),
+            uuid::Uuid::new_v4(),
+            uuid::Uuid::new_v4(),
+        );
+        let mut thread = Thread::with_notes(vec![a, c]);
+        thread.insert(1, ThreadItem::Task(b)).unwrap();
+        assert!(thread.insert(4, ThreadItem::Task(b)).is_err());
+        assert_eq!(thread.tasks(), vec![b]);
+        assert_eq!(thread.notes(), vec![a, c]);
+
+        thread.move_item(2, 0).unwrap();
+        assert_eq!(
+            thread.items,
+            vec![
+                ThreadItem::Note(c),
+                ThreadItem::Note(a),
+                ThreadItem::Task(b)
+            ]
+        );
+        assert!(thread.reorder(vec![ThreadItem::Note(a)]).is_err());
+        thread
+            .reorder(vec![
+                ThreadItem::Task(b),
+                ThreadItem::Note(a),
+                ThreadItem::Note(c),
+            ])
+            .unwrap();
+        assert!(thread.remove(ThreadItem::Note(a)));
+        assert!(!thread.remove(ThreadItem::Task(a)));
+        assert_eq!(thread.items, vec![ThreadItem::Task(b), ThreadItem::Note(c)]);
+    }
+
+    #[test]
+    fn slugs_are_url_safe() {
+        assert_eq!(slugify("Über Äpfel & Birnen"), "ueber-aepfel-birnen");
+        assert_eq!(
+            slugify("  Project: Q3/2021 -- Review! "),
+            "project-q3-2021-review"
+        );
+        assert_eq!(slugify("???"), "");
+    }
+}
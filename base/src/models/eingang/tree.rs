@@ -0,0 +1,155 @@
+//! Threads nested in other threads.
+//!
+//! A thread may contain other threads as items. The nesting must not contain
+//! cycles, so a thread can never be part of itself.
+use super::thread::{Thread, ThreadItem, ThreadUuid};
+use super::Idable;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// A thread together with its sub-threads up to a certain depth
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ThreadNode {
+    pub thread: Thread,
+    pub threads: Vec<ThreadNode>,
+}
+
+impl ThreadNode {
+    /// Expand the sub-threads of `thread` for `depth` levels. Sub-threads
+    /// which do not exist are left out.
+    pub fn new(thread: Thread, threads: &HashMap<ThreadUuid, Thread>, depth: usize) -> Self {
+        let mut path = HashSet::new();
+        ThreadNode::build(thread, threads, depth, &mut path)
+    }
+    fn build(
+        thread: Thread,
+        threads: &HashMap<ThreadUuid, Thread>,
+        depth: usize,
+        path: &mut HashSet<ThreadUuid>,
+    ) -> Self {
+        let mut children = Vec::new();
+        if depth > 0 {
+            path.insert(thread.get_uuid());
+            for sub in thread.threads() {
+                if path.contains(&sub) {
+                    continue;
+                }
+                if let Some(t) = threads.get(&sub) {
+                    children.push(ThreadNode::build(t.clone(), threads, depth - 1, path));
+                }
+            }
+            path.remove(&thread.get_uuid());
+        }
+        ThreadNode {
+            thread,
+            threads: children,
+        }
+    }
+}
+
+/// Return the cycle which would be created by adding `child` to `parent`,
+/// starting and ending with `parent`
+pub fn find_thread_cycle(
+    parent: ThreadUuid,
+    child: ThreadUuid,
+    threads: &HashMap<ThreadUuid, Thread>,
+) -> Option<Vec<ThreadUuid>> {
+    let mut visited = HashSet::new();
+    let mut stack = vec![(child, vec![parent, child])];
+    while let Some((current, path)) = stack.pop() {
+        if current == parent {
+            return Some(path);
+        }
+        if !visited.insert(current) {
+            continue;
+        }
+        if let Some(t) = threads.get(&current) {
+            for next in t.threads() {
+                let mut next_path = path.clone();
+                next_path.push(next);
+                stack.push((next, next_path));
+            }
+        }
+    }
+    None
+}
+
+/// Return all notes and tasks of the thread and its sub-threads in the order
+/// of the threads. Elements occurring several times are returned once.
+pub fn flatten(thread: &Thread, threads: &HashMap<ThreadUuid, Thread>) -> Vec<ThreadItem> {
+    let mut items = Vec::new();
+    let mut seen = HashSet::new();
+    let mut visited = HashSet::new();
+    collect(thread, threads, &mut visited, &mut seen, &mut items);
+    items
+}
+
+fn collect(
+    thread: &Thread,
+    threads: &HashMap<ThreadUuid, Thread>,
+    visited: &mut HashSet<ThreadUuid>,
+    seen: &mut HashSet<ThreadItem>,
+    items: &mut Vec<ThreadItem>,
+) {
+    if !visited.insert(thread.get_uuid()) {
+        return;
+    }
+    for item in &thread.items {
+        match item {
+            ThreadItem::Thread(sub) => {
+                if let Some(t) = threads.get(sub) {
+                    collect(t, threads, visited, seen, items);
+                }
+            }
+            _ => {
+                if seen.insert(*item) {
+                    items.push(*item);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_thread_cycle, flatten, ThreadNode};
+    use crate::models::eingang::thread::{Thread, ThreadItem};
+    use crate::models::eingang::Idable;
+    use std::collections::HashMap;
+
+    #[test]
+    fn expand_and_flatten_nested_threads() {
+        let (note, task) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+        let leaf = Thread::with_tasks_and_notes(vec![task], vec![note]);
+        let mut middle = Thread::with_notes(vec![note]);
+        middle.add_thread(leaf.get_uuid());
+        let mut root = Thread::new();
+        root.add_thread(middle.get_uuid());
+        let threads: HashMap<_, _> = vec![leaf.clone(), middle.clone(), root.clone()]
+            .into_iter()
+            .map(|t| (t.get_uuid(), t))
+            .collect();
+
+        let tree = ThreadNode::new(root.clone(), &threads, 1);
+        assert_eq!(tree.threads.len(), 1);
+        assert!(tree.threads[0].threads.is_empty());
+        let tree = ThreadNode::new(root.clone(), &threads, 5);
+        assert_eq!(tree.threads[0].threads.len(), 1);
+
+        assert_eq!(
+            flatten(&root, &threads),
+            vec![ThreadItem::Note(note), ThreadItem::Task(task)]
+        );
+        assert_eq!(
+            find_thread_cycle(leaf.get_uuid(), root.get_uuid(), &threads),
+            Some(vec![
+                leaf.get_uuid(),
+                root.get_uuid(),
+                middle.get_uuid(),
+                leaf.get_uuid()
+            ])
+        );
+        assert!(find_thread_cycle(root.get_uuid(), root.get_uuid(), &threads).is_some());
+        assert!(find_thread_cycle(root.get_uuid(), leaf.get_uuid(), &threads).is_none());
+    }
+}
@@ -0,0 +1,159 @@
+//! Urgency score of tasks, modelled after Taskwarrior.
+//!
+//! The score is the sum of several terms, each a value between 0 and 1
+//! weighted with a configurable coefficient:
+//!
+//! - priority: 1 for tasks with the respective priority
+//! - due: 0.2 for tasks due in more than 14 days, rising to 1 for tasks which
+//!   are overdue for 7 days or more
+//! - age: days since creation divided by `max_age`, at most 1
+//! - blocking: 1 if an active task is blocked by the task
+//! - blocked: 1 if the task is blocked by an active task
+//! - waiting: 1 for waiting tasks
+//! - tags: 0.8, 0.9 or 1 for one, two or more tags
+use super::dependency::is_blocking;
+use super::tag::Tagged;
+use super::task::{Priority, Task, TaskStatus};
+use super::{Idable, Timestamp};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Coefficients of the urgency terms
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct UrgencyCoefficients {
+    pub priority_high: f64,
+    pub priority_medium: f64,
+    pub priority_low: f64,
+    pub due: f64,
+    pub age: f64,
+    /// Age in days after which the age term is at its maximum
+    pub max_age: f64,
+    pub blocking: f64,
+    pub blocked: f64,
+    pub waiting: f64,
+    pub tags: f64,
+}
+
+impl Default for UrgencyCoefficients {
+    fn default() -> Self {
+        UrgencyCoefficients {
+            priority_high: 6.0,
+            priority_medium: 3.9,
+            priority_low: 1.8,
+            due: 12.0,
+            age: 2.0,
+            max_age: 365.0,
+            blocking: 8.0,
+            blocked: -5.0,
+            waiting: -3.0,
+            tags: 1.0,
+        }
+    }
+}
+
+/// A task together with its urgency score
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScoredTask {
+    #[serde(flatten)]
+    pub task: Task,
+    pub urgency: f64,
+}
+
+impl UrgencyCoefficients {
+    /// Urgency of a single task
+    pub fn urgency(&self, task: &Task, now: Timestamp, blocking: bool, blocked: bool) -> f64 {
+        let priority = match task.priority {
+            Priority::High => self.priority_high,
+            Priority::Medium => self.priority_medium,
+            Priority::Low => self.priority_low,
+            Priority::None => 0.0,
+        };
+        let due = task.due.map_or(0.0, |due| {
+            let overdue = (now - due).num_seconds() as f64 / 86400.0;
+            if overdue >= 7.0 {
+                1.0
+            } else if overdue >= -14.0 {
+                (overdue + 14.0) * 0.8 / 21.0 + 0.2
+            } else {
+                0.2
+            }
+        });
+        let age_days = (now - task.created_on()).num_seconds() as f64 / 86400.0;
+        let age = if self.max_age > 0.0 {
+            (age_days / self.max_age).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let tags = match task.tags().len() {
+            0 => 0.0,
+            1 => 0.8,
+            2 => 0.9,
+            _ => 1.0,
+        };
+        let flag = |b: bool| if b { 1.0 } else { 0.0 };
+        priority
+            + self.due * due
+            + self.age * age
+            + self.blocking * flag(blocking)
+            + self.blocked * flag(blocked)
+            + self.waiting * flag(task.status == TaskStatus::Waiting)
+            + self.tags * tags
+    }
+
+    /// Score all tasks and sort them by descending urgency. Ties are broken
+    /// by creation date and UUID, so the order is deterministic.
+    pub fn sort(&self, tasks: Vec<Task>, now: Timestamp) -> Vec<ScoredTask> {
+        let status: HashMap<_, _> = tasks.iter().map(|t| (t.get_uuid(), t.status)).collect();
+        let blocking: HashSet<_> = tasks
+            .iter()
+            .filter(|t| t.status.is_active())
+            .flat_map(|t| t.blocked_by.iter().copied())
+            .collect();
+        let mut scored: Vec<ScoredTask> = tasks
+            .into_iter()
+            .map(|task| {
+                let blocked = task
+                    .blocked_by
+                    .iter()
+                    .any(|b| status.get(b).is_some_and(|s| is_blocking(*s)));
+                let urgency =
+                    self.urgency(&task, now, blocking.contains(&task.get_uuid()), blocked);
+                ScoredTask { task, urgency }
+            })
+            .collect();
+        scored.sort_by(|a, b| {
+            b.urgency
+                .partial_cmp(&a.urgency)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.task.created_on().cmp(&b.task.created_on()))
+                .then_with(|| a.task.get_uuid().cmp(&b.task.get_uuid()))
+        });
+        scored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UrgencyCoefficients;
+    use crate::models::eingang::task::{Priority, Task};
+    use crate::models::eingang::Idable;
+
+    #[test]
+    fn sort_by_urgency() {
+        let now = chrono::Utc::now();
+        let plain = Task::new("plain".to_string());
+        let mut high = Task::new("high".to_string());
+        high.priority = Priority::High;
+        let mut overdue = Task::new("overdue".to_string());
+        overdue.due = Some(now - chrono::Duration::days(10));
+        let mut blocked = Task::new("blocked".to_string());
+        blocked.blocked_by = vec![plain.get_uuid()];
+
+        let coefficients = UrgencyCoefficients::default();
+        let sorted = coefficients.sort(vec![plain, high, overdue, blocked], now);
+        let order: Vec<&str> = sorted.iter().map(|s| s.task.content.as_str()).collect();
+        assert_eq!(order, vec!["overdue", "plain", "high", "blocked"]);
+        assert!((sorted[0].urgency - 12.0).abs() < 0.01);
+    }
+}
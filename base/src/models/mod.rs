@@ -1,11 +1,28 @@
 mod data;
 pub mod eingang;
 
-pub use self::eingang::journal::{JournalFilter, JournalQuery, JournalResponse, Period};
+pub use self::eingang::agenda::{Agenda, AgendaQuery};
+pub use self::eingang::dependency::{DependencyNode, DependencyQuery};
+pub use self::eingang::error::{ErrorKind, ErrorResponse};
+pub use self::eingang::expanded::{ExpandedItem, ExpandedThread};
+pub use self::eingang::fsck::{FsckReport, Problem};
+pub use self::eingang::journal::Period;
+pub use self::eingang::journal::{Bounds, During, JournalFilter, JournalQuery, JournalResponse};
+pub use self::eingang::link::{parse_links, LinkTarget, LinkedElement, LinksResponse};
 pub use self::eingang::note::{Note, NoteQuery};
-pub use self::eingang::task::{Task, TaskQuery, TaskStatus};
+pub use self::eingang::quarantine::Quarantined;
+pub use self::eingang::query::{Condition, ElementKind, Query, QueryRequest, Queryable, Term};
+pub use self::eingang::range::Range;
+pub use self::eingang::recurrence::Recurrence;
+pub use self::eingang::revision::{ChangeKind, DiffLine, DiffQuery, RevisionDiff, RevisionInfo};
+pub use self::eingang::search::{SearchHit, SearchQuery};
+pub use self::eingang::tag::{filter_by_tag, normalize_tags, TagCount, Tagged, Tags};
+pub use self::eingang::task::{Priority, StatusChange, Task, TaskQuery, TaskSort, TaskStatus};
 pub use self::eingang::thread::{
-    NoteUuid, TaskUuid, Thread, ThreadFilter, ThreadQuery, ThreadResponse,
+    slugify, NoteUuid, ReorderQuery, TaskUuid, Thread, ThreadFilter, ThreadItem, ThreadQuery,
+    ThreadResponse, ThreadState, ThreadUuid,
 };
+pub use self::eingang::tree::ThreadNode;
+pub use self::eingang::urgency::{ScoredTask, UrgencyCoefficients};
 pub use self::eingang::Idable;
 pub use data::Data;